@@ -1,7 +1,8 @@
 MEMORY
 {
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  /* the last 64K of the 256K is kept for saving a game in progress, see src/flash.rs */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 192K
   RAM : ORIGIN = 0x20000000, LENGTH = 32K
 }
 
@@ -0,0 +1,137 @@
+use core::cell::Cell;
+
+// Analog inputs, all sampled by ADC0's sample sequencer 0
+// step | pin | channel | signal
+//   0  | PB5 | AIN11   | joystick X
+//   1  | PD3 | AIN4    | joystick Y
+//   2  | PD0 | AIN7    | accelerometer X
+//   3  | PD1 | AIN6    | accelerometer Y
+//   4  | PD2 | AIN5    | accelerometer Z
+//   5  |  -  | TS      | internal temperature sensor
+//
+// During start up the sequence is kicked off by software (see `sample`). Once the game is
+// running it is triggered by Timer0 at the start of every frame, so every frame gets one
+// snapshot that was taken at the same point in time. ADC1 isn't used.
+
+/// Number of steps in the sample sequence
+const STEPS : usize = 6;
+/// Hardware averaging: 2^4 = 16 samples are averaged for every step
+const OVERSAMPLING : u32 = 4;
+
+const SS0_BIT : u32 = 1;
+
+/// One reading of every analog input, all from the same sequence
+#[derive(Clone, Copy, Default)]
+pub struct AdcSnapshot {
+    pub joystick_x: u16,
+    pub joystick_y: u16,
+    pub accel_x: u16,
+    pub accel_y: u16,
+    pub accel_z: u16,
+    pub temperature: u16,
+}
+
+pub struct Adc<'a> {
+    p: &'a tm4c123x::Peripherals,
+    /// the most recent complete snapshot
+    last: Cell<AdcSnapshot>,
+}
+
+impl Adc<'_> {
+    pub fn new(p: &tm4c123x::Peripherals) -> Adc {
+        // 1. enable ADC0
+        p.SYSCTL.rcgcadc.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) } );
+        // 2. enable clock for the B and D ports                      DCBA
+        p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits (r.bits() | 0b1010) } );
+
+        // wait for port B and D to be ready for use
+        while p.SYSCTL.prgpio.read().bits() & 0b1010 != 0b1010 {}
+
+        // 3. set AFSEL bits for PB5 and PD0-PD3                           54 3210
+        p.GPIO_PORTB_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b10_0000 ) }  );
+        //                                                                 3210
+        p.GPIO_PORTD_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b1111 ) }  );
+        // 4. configure PB5 and PD0-PD3 as analog by clearing GPIODEN bits
+        p.GPIO_PORTB_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() & (!0b10_0000) ) } );
+        p.GPIO_PORTD_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1111) ) } );
+        // 5. disable analog isolation circuit for the ADC input pins
+        p.GPIO_PORTB_AHB.amsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b10_0000 ) }  );
+        p.GPIO_PORTD_AHB.amsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b1111 ) }  );
+
+        // wait for the ADC to be ready
+        while p.SYSCTL.pradc.read().bits() & 1 != 1 {}
+
+        // 6. disable sample sequencer 0
+        p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() & (!SS0_BIT) ) } );
+        // 7. start out triggered by the processor (EM0 = 0)
+        p.ADC0.emux.modify(|r, w| unsafe { w.bits( r.bits() & (!0xF) ) } );
+        // 8. configure the input source for each step (4 bits per step)
+        //    the temperature sensor step ignores its mux value
+        p.ADC0.ssmux0.write(|w| unsafe { w.bits( 11 | (4 << 4) | (7 << 8) | (6 << 12) | (5 << 16) ) } );
+        // 9. configure the sample control bits (4 bits per step)
+        //    [TS | IE | END | D], only the last step is special
+        //    1110 -> temperature sensor, set the interrupt flag, end of sequence
+        p.ADC0.ssctl0.write(|w| unsafe { w.bits( 0b1110 << (4 * (STEPS - 1)) ) });
+        // 10. hardware averaging
+        p.ADC0.sac.write(|w| unsafe { w.bits(OVERSAMPLING) });
+        // 11. enable sample sequencer 0
+        p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() | SS0_BIT ) } );
+
+        Adc {
+            p,
+            last: Cell::new(Default::default()),
+        }
+    }
+
+    /// Take a snapshot right now and wait for it. Only works before `start_timer_trigger` is called.
+    pub fn sample(&self) -> AdcSnapshot {
+        self.p.ADC0.pssi.write(|w| unsafe { w.bits(SS0_BIT) });
+        while self.p.ADC0.ris.read().bits() & SS0_BIT == 0 {}
+        self.read_fifo()
+    }
+
+    /// From now on a snapshot is taken every time a timer with its ADC trigger enabled times out
+    pub fn start_timer_trigger(&self) {
+        self.p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() & (!SS0_BIT) ) } );
+        // EM0 = 0x5 -> timer
+        self.p.ADC0.emux.modify(|r, w| unsafe { w.bits( (r.bits() & (!0xF)) | 0x5 ) } );
+        self.p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() | SS0_BIT ) } );
+    }
+
+    /// Wait for this frame's snapshot. If the FIFO overflowed because a frame ran long,
+    /// the FIFO is flushed and the previous snapshot is returned.
+    pub fn snapshot(&self) -> AdcSnapshot {
+        if self.p.ADC0.ostat.read().bits() & SS0_BIT != 0 {
+            // the steps in the FIFO are no longer lined up, throw them all away
+            while self.p.ADC0.ssfstat0.read().bits() & 0x100 == 0 {
+                self.p.ADC0.ssfifo0.read();
+            }
+            self.p.ADC0.ostat.write(|w| unsafe { w.bits(SS0_BIT) });
+            self.p.ADC0.isc.write(|w| unsafe { w.bits(SS0_BIT) });
+            return self.last.get();
+        }
+
+        while self.p.ADC0.ris.read().bits() & SS0_BIT == 0 {}
+        self.read_fifo()
+    }
+
+    fn read_fifo(&self) -> AdcSnapshot {
+        let mut readings = [0u16; STEPS];
+        for reading in readings.iter_mut() {
+            *reading = (self.p.ADC0.ssfifo0.read().bits() & 0xFFF) as u16;
+        }
+        // clear the SS0 interrupt status
+        self.p.ADC0.isc.write(|w| unsafe { w.bits(SS0_BIT) });
+
+        let snapshot = AdcSnapshot {
+            joystick_x: readings[0],
+            joystick_y: readings[1],
+            accel_x: readings[2],
+            accel_y: readings[3],
+            accel_z: readings[4],
+            temperature: readings[5],
+        };
+        self.last.set(snapshot);
+        snapshot
+    }
+}
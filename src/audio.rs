@@ -0,0 +1,300 @@
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU8, Ordering};
+
+use tm4c123x::interrupt;
+
+use crate::clock::SYSTEM_CLOCK_HZ;
+use crate::music::{self, Player};
+use crate::timer::{self, Config, Timer, TimerId};
+
+// Sound effects and music on the BoosterPack's buzzer, which sits on PF2.
+//
+// TIMER1 makes the tone: PWM on T1CCP0 (PF2) at the note's frequency, with a 50% duty cycle.
+// TIMER2 plays the notes: it interrupts every millisecond, and the handler moves on to the next
+// note when it's time. So nothing here ever waits, `play` just leaves a request for the handler
+// and returns. TIMER2 only runs while something is playing.
+//
+// A new effect cuts off whatever is playing, unless what's playing is more important
+// (a line clear doesn't get cut off by the next move).
+//
+// The music (see music.rs) is quieted while an effect plays over it, but it keeps going, so it
+// comes back in where it would have been.
+
+/// How often the sequencer interrupt fires
+const TICK_HZ : u32 = 1000;
+
+const BUZZER_TIMER : TimerId = TimerId::Timer1;
+const SEQUENCER_TIMER : TimerId = TimerId::Timer2;
+const PF2 : u32 = 0x04;
+
+/// One note of an effect
+#[derive(Clone, Copy)]
+struct Tone {
+    /// 0 for a rest
+    hz: u16,
+    ms: u16,
+}
+
+const fn tone(hz: u16, ms: u16) -> Tone {
+    Tone { hz, ms }
+}
+
+const MOVE : [Tone; 1] = [tone(1200, 8)];
+const ROTATE : [Tone; 1] = [tone(1600, 12)];
+const LOCK : [Tone; 1] = [tone(220, 25)];
+const SINGLE : [Tone; 2] = [tone(660, 40), tone(880, 60)];
+const DOUBLE : [Tone; 3] = [tone(660, 40), tone(880, 40), tone(1100, 60)];
+const TRIPLE : [Tone; 4] = [tone(660, 35), tone(880, 35), tone(1100, 35), tone(1320, 70)];
+const TETRIS : [Tone; 6] = [tone(880, 50), tone(1100, 50), tone(1320, 50), tone(1760, 60), tone(0, 30), tone(1760, 120)];
+const LEVEL_UP : [Tone; 4] = [tone(523, 60), tone(659, 60), tone(784, 60), tone(1047, 150)];
+const GAME_OVER : [Tone; 4] = [tone(392, 150), tone(370, 150), tone(349, 150), tone(330, 400)];
+
+/// Everything that makes a sound. They're numbered from least to most important,
+/// so when two get asked for before the handler runs the bigger number wins.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Effect {
+    Move = 1,
+    Rotate,
+    Lock,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    LevelUp,
+    GameOver,
+}
+
+impl Effect {
+    /// The line clear effect for `lines` lines (1-4)
+    pub fn lines(lines: u32) -> Effect {
+        match lines {
+            0 | 1 => Effect::Single,
+            2 => Effect::Double,
+            3 => Effect::Triple,
+            _ => Effect::Tetris,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Effect> {
+        const EFFECTS : [Effect; 9] = [
+            Effect::Move,
+            Effect::Rotate,
+            Effect::Lock,
+            Effect::Single,
+            Effect::Double,
+            Effect::Triple,
+            Effect::Tetris,
+            Effect::LevelUp,
+            Effect::GameOver,
+        ];
+        EFFECTS.iter().copied().find(|effect| *effect as u8 == code)
+    }
+
+    /// An effect can cut off anything with the same priority or lower
+    fn priority(self) -> u8 {
+        match self {
+            Effect::Move | Effect::Rotate => 0,
+            Effect::Lock => 1,
+            Effect::Single | Effect::Double | Effect::Triple | Effect::Tetris => 2,
+            Effect::LevelUp => 3,
+            Effect::GameOver => 4,
+        }
+    }
+
+    fn tones(self) -> &'static [Tone] {
+        match self {
+            Effect::Move => &MOVE,
+            Effect::Rotate => &ROTATE,
+            Effect::Lock => &LOCK,
+            Effect::Single => &SINGLE,
+            Effect::Double => &DOUBLE,
+            Effect::Triple => &TRIPLE,
+            Effect::Tetris => &TETRIS,
+            Effect::LevelUp => &LEVEL_UP,
+            Effect::GameOver => &GAME_OVER,
+        }
+    }
+}
+
+/// The effect `play` asked for, 0 for none. Only the handler clears it.
+static REQUEST : AtomicU8 = AtomicU8::new(0);
+/// Set by `stop`, the handler forgets what it was playing
+static CANCEL : AtomicBool = AtomicBool::new(false);
+
+// MUSIC values
+const MUSIC_STOPPED : u8 = 0;
+const MUSIC_PLAYING : u8 = 1;
+const MUSIC_PAUSED : u8 = 2;
+/// What the music should be doing
+static MUSIC : AtomicU8 = AtomicU8::new(MUSIC_STOPPED);
+/// Set by `stop_music`, the handler goes back to the top of the song. This can't be left to the
+/// handler noticing MUSIC_STOPPED, it might not run again until `play_music` starts it.
+static RESTART : AtomicBool = AtomicBool::new(false);
+/// How long a sixteenth note lasts, the tempo goes up with the level
+static SIXTEENTH_MS : AtomicU16 = AtomicU16::new(0);
+
+pub struct Audio<'a> {
+    buzzer: Timer<'a>,
+    sequencer: Timer<'a>,
+}
+
+impl<'a> Audio<'a> {
+    pub fn new(p: &'a tm4c123x::Peripherals) -> Audio<'a> {
+        // 1. enable the clock for port F
+        p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | 0x20 ) });
+        while p.SYSCTL.prgpio.read().bits() & 0x20 == 0 {}
+
+        // 2. PF2 is T1CCP0 (PMC value of 7)
+        p.GPIO_PORTF_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | PF2 ) });
+        p.GPIO_PORTF_AHB.pctl.modify(|r, w| unsafe { w.bits( (r.bits() & !0xF00) | 0x700 ) });
+        p.GPIO_PORTF_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | PF2 ) });
+
+        // 3. the buzzer timer runs all the time, silence is a 0% duty cycle
+        let mut buzzer = Timer::take(p, BUZZER_TIMER).unwrap();
+        buzzer.configure(&Config::pwm(SYSTEM_CLOCK_HZ / TICK_HZ));
+        buzzer.start();
+
+        // 4. the sequencer waits for something to play
+        let mut sequencer = Timer::take(p, SEQUENCER_TIMER).unwrap();
+        sequencer.configure(&Config::periodic(SYSTEM_CLOCK_HZ / TICK_HZ));
+        sequencer.enable_interrupt();
+
+        Audio {
+            buzzer,
+            sequencer,
+        }
+    }
+
+    /// Start playing `effect`, unless something more important is playing
+    pub fn play(&self, effect: Effect) {
+        REQUEST.fetch_max(effect as u8, Ordering::Relaxed);
+        self.sequencer.start();
+    }
+
+    /// Play the music (or carry on with it), at the tempo for `level`
+    pub fn play_music(&self, level: usize) {
+        SIXTEENTH_MS.store(music::sixteenth_ms(level), Ordering::Relaxed);
+        MUSIC.store(MUSIC_PLAYING, Ordering::Relaxed);
+        self.sequencer.start();
+    }
+
+    /// Hold the music where it is, `play_music` picks up from there
+    pub fn pause_music(&self) {
+        if MUSIC.load(Ordering::Relaxed) == MUSIC_PLAYING {
+            MUSIC.store(MUSIC_PAUSED, Ordering::Relaxed);
+        }
+    }
+
+    /// Stop the music, it starts from the top next time
+    pub fn stop_music(&self) {
+        MUSIC.store(MUSIC_STOPPED, Ordering::Relaxed);
+        RESTART.store(true, Ordering::Relaxed);
+    }
+
+    /// Cut off whatever is playing and keep quiet, like before going to sleep.
+    /// The music is only held where it is.
+    pub fn stop(&self) {
+        self.sequencer.stop();
+        REQUEST.store(0, Ordering::Relaxed);
+        CANCEL.store(true, Ordering::Relaxed);
+        let interval = SYSTEM_CLOCK_HZ / TICK_HZ;
+        self.buzzer.set_interval(interval);
+        self.buzzer.set_match(interval);
+    }
+}
+
+/// What the handler is playing
+struct Voice {
+    tones: &'static [Tone],
+    /// the note that's playing
+    index: usize,
+    /// ms left on it
+    remaining: u16,
+    priority: u8,
+}
+
+/// Put a note on the buzzer, `hz` of 0 is silence. This is Timer::set_interval and
+/// Timer::set_match for the split TIMER1, the handler doesn't have the Timer.
+fn set_tone(hz: u16) {
+    let timer = BUZZER_TIMER.registers();
+    let (interval, high_ticks) =
+        if hz == 0 {
+            (SYSTEM_CLOCK_HZ / TICK_HZ, 0)
+        } else {
+            let interval = SYSTEM_CLOCK_HZ / hz as u32;
+            (interval, interval / 2)
+        };
+    let match_value = interval - high_ticks;
+    timer.tapr.write(|w| unsafe { w.bits( interval >> 16 ) });
+    timer.tailr.write(|w| unsafe { w.bits( interval & 0xFFFF ) });
+    timer.tapmr.write(|w| unsafe { w.bits( match_value >> 16 ) });
+    timer.tamatchr.write(|w| unsafe { w.bits( match_value & 0xFFFF ) });
+}
+
+#[interrupt]
+fn TIMER2A() {
+    static mut VOICE: Option<Voice> = None;
+    static mut PLAYER: Player = Player::new();
+    /// what's on the buzzer, it's only touched when that changes (writing the
+    /// load value starts the period over)
+    static mut HZ: u16 = 0;
+
+    let sequencer = SEQUENCER_TIMER.registers();
+    sequencer.icr.write(|w| unsafe { w.bits(timer::TIMEOUT) });
+
+    if CANCEL.swap(false, Ordering::Relaxed) {
+        // `stop` already silenced the buzzer
+        *VOICE = None;
+        *HZ = 0;
+    }
+
+    // 1. something new to play?
+    if let Some(effect) = Effect::from_code(REQUEST.swap(0, Ordering::Relaxed)) {
+        if VOICE.as_ref().map_or(true, |voice| effect.priority() >= voice.priority) {
+            let tones = effect.tones();
+            *VOICE = Some(Voice {
+                tones,
+                index: 0,
+                remaining: tones[0].ms,
+                priority: effect.priority(),
+            });
+        }
+    }
+
+    // 2. the music moves on whether it can be heard or not
+    if RESTART.swap(false, Ordering::Relaxed) {
+        PLAYER.restart();
+    }
+    let music = match MUSIC.load(Ordering::Relaxed) {
+        MUSIC_PLAYING => Some(PLAYER.tick(&music::KOROBEINIKI, SIXTEENTH_MS.load(Ordering::Relaxed))),
+        _ => None,
+    };
+
+    // 3. the effect's note, and on to the next one when it's time
+    let effect = VOICE.as_mut().map(|voice| {
+        let hz = voice.tones[voice.index].hz;
+        voice.remaining = voice.remaining.saturating_sub(1);
+        if voice.remaining == 0 {
+            voice.index += 1;
+            if let Some(tone) = voice.tones.get(voice.index) {
+                voice.remaining = tone.ms;
+            }
+        }
+        hz
+    });
+    if VOICE.as_ref().map_or(false, |voice| voice.index >= voice.tones.len()) {
+        *VOICE = None;
+    }
+
+    // 4. effects drown out the music
+    let hz = effect.or(music).unwrap_or(0);
+    if hz != *HZ {
+        set_tone(hz);
+        *HZ = hz;
+    }
+
+    if effect.is_none() && music.is_none() {
+        // nothing left to play, sleep until `play` or `play_music` starts the timer again
+        // (clear TAEN)
+        sequencer.ctl.modify(|r, w| unsafe { w.bits( r.bits() & !1 ) });
+    }
+}
@@ -0,0 +1,133 @@
+use crate::clock::SYSTEM_CLOCK_HZ;
+
+// LCD backlight brightness, with PWM on PF3 (M1PWM7: PWM module 1, generator 3, output B).
+// The BoosterPack only connects PF3 to the backlight when its jumper is set that way,
+// otherwise the backlight is always on and none of this does anything.
+//
+// Brightness fades towards its target a little every frame, and drops down to DIM_PERCENT
+// when nobody has touched anything for a while.
+
+/// 20 kHz, too fast to see flicker
+const PWM_FREQUENCY_HZ : u32 = 20_000;
+/// PWM clock is the system clock (no divider), so this is the number of counts per period
+const PERIOD : u32 = SYSTEM_CLOCK_HZ / PWM_FREQUENCY_HZ;
+/// brightness while dimmed
+const DIM_PERCENT : u8 = 10;
+/// how long without any input before the backlight dims (30 s)
+const DIM_AFTER_TICKS : u32 = SYSTEM_CLOCK_HZ * 30;
+/// how far the brightness moves towards the target every update, in percent
+const FADE_STEP : u8 = 2;
+
+// GENB values: what output B does when the counter (counting down) hits...
+/// high at LOAD, low at CMPB on the way down
+const GEN_PWM : u32 = 0x80C;
+/// low at LOAD and at zero, so always low
+const GEN_ALWAYS_LOW : u32 = 0x00A;
+/// high at LOAD and at zero, so always high
+const GEN_ALWAYS_HIGH : u32 = 0x00F;
+
+const PF3 : u32 = 0x08;
+
+pub struct Backlight<'a> {
+    p: &'a tm4c123x::Peripherals,
+    /// what the player asked for
+    brightness: u8,
+    /// what the backlight is fading towards
+    target: u8,
+    /// what the backlight is at right now
+    current: u8,
+    /// clock ticks since the last input
+    idle_ticks: u32,
+}
+
+impl<'a> Backlight<'a> {
+    /// `brightness` is in percent
+    pub fn new(p: &'a tm4c123x::Peripherals, brightness: u8) -> Backlight<'a> {
+        // 1. enable the clock for PWM module 1 and port F
+        p.SYSCTL.rcgcpwm.modify(|r, w| unsafe { w.bits( r.bits() | 0b10 ) });
+        p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | 0x20 ) });
+        while p.SYSCTL.prpwm.read().bits() & 0b10 == 0 {}
+        while p.SYSCTL.prgpio.read().bits() & 0x20 == 0 {}
+
+        // 2. PF3 is M1PWM7 (PMC value of 5)
+        p.GPIO_PORTF_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | PF3 ) });
+        p.GPIO_PORTF_AHB.pctl.modify(|r, w| unsafe { w.bits( (r.bits() & !0xF000) | 0x5000 ) });
+        p.GPIO_PORTF_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | PF3 ) });
+
+        // 3. no PWM clock divider (USEPWMDIV = 0), the PWM runs off the system clock
+        p.SYSCTL.rcc.modify(|r, w| unsafe { w.bits( r.bits() & !(1 << 20) ) });
+
+        // 4. disable generator 3 while it's set up, count down mode
+        p.PWM1._3_ctl.write(|w| unsafe { w.bits(0) });
+        // 5. period
+        p.PWM1._3_load.write(|w| unsafe { w.bits(PERIOD - 1) });
+
+        let mut backlight = Backlight {
+            p,
+            brightness,
+            target: brightness,
+            current: brightness,
+            idle_ticks: 0,
+        };
+        backlight.apply();
+
+        // 6. start the generator and turn on output 7
+        p.PWM1._3_ctl.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        p.PWM1.enable.modify(|r, w| unsafe { w.bits( r.bits() | (1 << 7) ) });
+        backlight
+    }
+
+    /// Fade to `percent` (0-100)
+    pub fn set_brightness(&mut self, percent: u8) {
+        self.brightness = if percent > 100 { 100 } else { percent };
+        self.target = self.brightness;
+        self.idle_ticks = 0;
+    }
+
+    /// Switch off right away, the next active update fades back in
+    pub fn off(&mut self) {
+        self.current = 0;
+        self.target = 0;
+        self.apply();
+    }
+
+    /// Call once per frame. `active` is true if the player did anything this frame.
+    pub fn update(&mut self, active: bool, elapsed_ticks: u32) {
+        if active {
+            self.idle_ticks = 0;
+            self.target = self.brightness;
+        } else {
+            self.idle_ticks = self.idle_ticks.saturating_add(elapsed_ticks);
+            if self.idle_ticks >= DIM_AFTER_TICKS {
+                self.target = DIM_PERCENT.min(self.brightness);
+            }
+        }
+
+        if self.current == self.target {
+            return;
+        }
+        self.current =
+            if self.current < self.target {
+                (self.current + FADE_STEP).min(self.target)
+            } else {
+                self.current.saturating_sub(FADE_STEP).max(self.target)
+            };
+        self.apply();
+    }
+
+    /// Set the duty cycle to `current`
+    fn apply(&self) {
+        let pwm = &self.p.PWM1;
+        match self.current {
+            // the compare can't quite reach 0% or 100%, so those get special treatment
+            0 => pwm._3_genb.write(|w| unsafe { w.bits(GEN_ALWAYS_LOW) }),
+            100 => pwm._3_genb.write(|w| unsafe { w.bits(GEN_ALWAYS_HIGH) }),
+            percent => {
+                // high from LOAD down to CMPB
+                let high = PERIOD * percent as u32 / 100;
+                pwm._3_cmpb.write(|w| unsafe { w.bits(PERIOD - 1 - high) });
+                pwm._3_genb.write(|w| unsafe { w.bits(GEN_PWM) });
+            },
+        }
+    }
+}
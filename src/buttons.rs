@@ -0,0 +1,200 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use cortex_m::peripheral::NVIC;
+use tm4c123x::{interrupt, Interrupt};
+
+use crate::timer::{self, FrameTime};
+
+// Buttons on the BoosterPack, they pull the pins to ground when pressed
+// PD6 - S1
+// PD7 - S2
+// PE4 - joystick select
+//
+// Every edge on these pins fires an interrupt, which pushes a timestamped event into a queue.
+// The main loop pulls the events out once per frame, so presses shorter than a frame
+// aren't lost and we know what order things happened in. The timestamps also say how long
+// events sat in the queue, which the profiler reports (see take_worst_latency).
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Button {
+    S1,
+    S2,
+    Select,
+}
+
+#[derive(Clone, Copy)]
+pub struct ButtonEvent {
+    pub button: Button,
+    /// true when the button was pushed down, false when it was let go
+    pub pressed: bool,
+    /// when the edge was seen
+    pub timestamp: FrameTime,
+}
+
+/// must be a power of 2
+const QUEUE_SIZE : usize = 32;
+
+/// A lock-free queue with one producer (the GPIO interrupts, which run at the same priority
+/// and so never interrupt each other) and one consumer (the main loop)
+struct EventQueue {
+    events: UnsafeCell<[ButtonEvent; QUEUE_SIZE]>,
+    /// next event to read, only written by the consumer
+    head: AtomicUsize,
+    /// next free slot, only written by the producer
+    tail: AtomicUsize,
+}
+
+// the head and tail indices make sure the producer and consumer never touch the same slot
+unsafe impl Sync for EventQueue {}
+
+impl EventQueue {
+    const fn new() -> EventQueue {
+        EventQueue {
+            events: UnsafeCell::new([ButtonEvent { button: Button::S1, pressed: false, timestamp: FrameTime { frame: 0, ticks: 0 } }; QUEUE_SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns false if the queue was full and the event was thrown away
+    fn push(&self, event: ButtonEvent) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == QUEUE_SIZE {
+            return false;
+        }
+        unsafe {
+            (*self.events.get())[tail % QUEUE_SIZE] = event;
+        }
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    fn pop(&self) -> Option<ButtonEvent> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let event = unsafe { (*self.events.get())[head % QUEUE_SIZE] };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(event)
+    }
+}
+
+static EVENTS : EventQueue = EventQueue::new();
+/// number of events thrown away because nobody was emptying the queue (handy in the debugger)
+static DROPPED_EVENTS : AtomicUsize = AtomicUsize::new(0);
+/// longest an event has waited in the queue, in clock ticks, since take_worst_latency
+static WORST_LATENCY : AtomicU32 = AtomicU32::new(0);
+
+const S1_PIN : u32 = 0b0100_0000;
+const S2_PIN : u32 = 0b1000_0000;
+const SELECT_PIN : u32 = 0b1_0000;
+
+// Configure PD6, PD7, and PE4 as inputs for the push buttons and joystick. That's enough for
+// is_pressed, the events need enable_interrupts too.
+pub fn initialize(p: &tm4c123x::Peripherals) {
+    // --------------------------------------------------
+    // initialize GPIO pins for push buttons and joystick
+    // These buttons pull the pins to ground
+    // there are pull-up resistors on the pcb
+    // --------------------------------------------------
+    // 1. enable clock for ports D and E (bits 3 and 4)                     E DBCA
+    p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | 0b1_1000 )});
+
+    // wait for ports D and E to be ready
+    while p.SYSCTL.prgpio.read().bits() & 0b1_1000 != 0b1_1000 {}
+    // unlock the GPIOCR register, and modify it so we can configure PD7
+    p.GPIO_PORTD_AHB.lock.write(|w| unsafe { w.bits( 0x4C4F434B ) });
+    p.GPIO_PORTD_AHB.cr.modify(|r, w| unsafe { w.bits( r.bits() | 0x80 ) });
+    // 2. set pins D6 and D7 as input                                            7654 3210
+    p.GPIO_PORTD_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1100_0000) )});
+    p.GPIO_PORTD_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() |   0b1100_0000)});
+    // 3. set pin E4 as input                                                    4 3210
+    p.GPIO_PORTE_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1_0000) )});
+    p.GPIO_PORTE_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0b1_0000 )});
+}
+
+/// Start queueing events, with interrupts on both edges. The interrupts timestamp the events with
+/// timer::frame_time, which reads TIMER0, so Timer0 has to be set up first: touching a timer
+/// whose clock is off is a bus fault.
+pub fn enable_interrupts(p: &tm4c123x::Peripherals) {
+    // 1. mask the interrupts while we configure them
+    p.GPIO_PORTD_AHB.im.modify(|r, w| unsafe { w.bits( r.bits() & !(S1_PIN | S2_PIN) )});
+    p.GPIO_PORTE_AHB.im.modify(|r, w| unsafe { w.bits( r.bits() & !SELECT_PIN )});
+    // 2. edge sensitive (IS = 0), on both edges (IBE = 1)
+    p.GPIO_PORTD_AHB.is.modify(|r, w| unsafe { w.bits( r.bits() & !(S1_PIN | S2_PIN) )});
+    p.GPIO_PORTD_AHB.ibe.modify(|r, w| unsafe { w.bits( r.bits() | S1_PIN | S2_PIN )});
+    p.GPIO_PORTE_AHB.is.modify(|r, w| unsafe { w.bits( r.bits() & !SELECT_PIN )});
+    p.GPIO_PORTE_AHB.ibe.modify(|r, w| unsafe { w.bits( r.bits() | SELECT_PIN )});
+    // 3. clear anything that might have been latched while we were setting up
+    p.GPIO_PORTD_AHB.icr.write(|w| unsafe { w.bits( S1_PIN | S2_PIN )});
+    p.GPIO_PORTE_AHB.icr.write(|w| unsafe { w.bits( SELECT_PIN )});
+    // 4. unmask the interrupts
+    p.GPIO_PORTD_AHB.im.modify(|r, w| unsafe { w.bits( r.bits() | S1_PIN | S2_PIN )});
+    p.GPIO_PORTE_AHB.im.modify(|r, w| unsafe { w.bits( r.bits() | SELECT_PIN )});
+    // 5. enable the interrupts in the NVIC
+    unsafe {
+        NVIC::unmask(Interrupt::GPIOD);
+        NVIC::unmask(Interrupt::GPIOE);
+    }
+}
+
+/// Is the button being held down right now?
+pub fn is_pressed(p: &tm4c123x::Peripherals, button: Button) -> bool {
+    match button {
+        Button::S1 => p.GPIO_PORTD_AHB.data.read().bits() & S1_PIN == 0,
+        Button::S2 => p.GPIO_PORTD_AHB.data.read().bits() & S2_PIN == 0,
+        Button::Select => p.GPIO_PORTE_AHB.data.read().bits() & SELECT_PIN == 0,
+    }
+}
+
+/// The oldest event that hasn't been handled yet
+pub fn next_event() -> Option<ButtonEvent> {
+    let event = EVENTS.pop()?;
+    let latency = timer::frame_time().ticks_since(event.timestamp);
+    WORST_LATENCY.fetch_max(latency, Ordering::Relaxed);
+    Some(event)
+}
+
+/// The longest time between a button's edge and `next_event` handing it out, in clock ticks,
+/// since the last call
+pub fn take_worst_latency() -> u32 {
+    WORST_LATENCY.swap(0, Ordering::Relaxed)
+}
+
+fn push(button: Button, pressed: bool, timestamp: FrameTime) {
+    if !EVENTS.push(ButtonEvent { button, pressed, timestamp }) {
+        DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[interrupt]
+fn GPIOD() {
+    let timestamp = timer::frame_time();
+    let port = unsafe { &*tm4c123x::GPIO_PORTD_AHB::ptr() };
+    let status = port.mis.read().bits();
+    port.icr.write(|w| unsafe { w.bits(status) });
+
+    let data = port.data.read().bits();
+    if status & S1_PIN != 0 {
+        push(Button::S1, data & S1_PIN == 0, timestamp);
+    }
+    if status & S2_PIN != 0 {
+        push(Button::S2, data & S2_PIN == 0, timestamp);
+    }
+}
+
+#[interrupt]
+fn GPIOE() {
+    let timestamp = timer::frame_time();
+    let port = unsafe { &*tm4c123x::GPIO_PORTE_AHB::ptr() };
+    let status = port.mis.read().bits();
+    port.icr.write(|w| unsafe { w.bits(status) });
+
+    if status & SELECT_PIN != 0 {
+        push(Button::Select, port.data.read().bits() & SELECT_PIN == 0, timestamp);
+    }
+}
@@ -0,0 +1,47 @@
+// How fast the system clock is. pll.rs sets it up: 16 MHz crystal -> PLL (400 MHz) -> divide
+// by 5 -> 80 MHz, the fastest the part can go.
+//
+// Everything that cares about the clock speed (timer intervals, baud rates, SPI clock,
+// delays) works it out from the constants here.
+
+/// Frequency of the system clock, which drives the CPU, timers, SSI and UART
+pub const SYSTEM_CLOCK_HZ : u32 = 80_000_000;
+
+/// SSI clock = system clock / (CPSDVSR * (1 + SCR)), CPSDVSR has to be even and 2-254.
+/// Returns (CPSDVSR, SCR) for the fastest SSI clock that isn't over `max_hz`.
+pub const fn ssi_divisors(max_hz: u32) -> (u32, u32) {
+    // total divisor, rounded up so we stay at or under max_hz
+    let divisor = SYSTEM_CLOCK_HZ.div_ceil(max_hz);
+    // CPSDVSR = 2 covers everything up to 512, past that the prescaler has to do some work
+    let mut cpsdvsr = 2;
+    while divisor.div_ceil(cpsdvsr) > 256 {
+        cpsdvsr += 2;
+    }
+    let scr = divisor.div_ceil(cpsdvsr) - 1;
+    (cpsdvsr, scr)
+}
+
+/// UART baud rate divisor = system clock / (16 * baud), split into a 16 bit integer part
+/// and a 6 bit fraction (in 64ths). Returns (IBRD, FBRD).
+pub const fn uart_divisors(baud: u32) -> (u32, u32) {
+    // divisor * 64, rounded: clock * 4 / baud
+    let divisor_64ths = (SYSTEM_CLOCK_HZ / baud * 4) + ((SYSTEM_CLOCK_HZ % baud) * 4 + baud / 2) / baud;
+    (divisor_64ths >> 6, divisor_64ths & 0x3F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divisors() {
+        // 80 MHz / (16 * 115200) = 43.40, and 0.40 * 64 = 26
+        assert_eq!(uart_divisors(115_200), (43, 26));
+        // 80 MHz / 20 MHz = 4
+        assert_eq!(ssi_divisors(20_000_000), (2, 1));
+        // 80 MHz / 15 MHz = 5.3, rounded up to 6 so it stays under
+        assert_eq!(ssi_divisors(15_000_000), (2, 2));
+        // too slow for CPSDVSR = 2: 80 MHz / 100 kHz = 800 = 4 * 200
+        assert_eq!(ssi_divisors(100_000), (4, 199));
+    }
+}
@@ -0,0 +1,164 @@
+use fourtris::game::Input;
+
+use crate::adc::AdcSnapshot;
+use crate::buttons::{self, Button};
+use crate::input::{decode, Actions, InputSource};
+use crate::settings::{Sensitivity, Settings};
+use crate::uart::Uart0;
+
+// The input sources that need the hardware, see input.rs for the rest
+
+/// The joystick and buttons on the BoosterPack-MKII
+pub struct BoosterPack {
+    /// this frame's analog readings, see set_snapshot
+    snapshot: AdcSnapshot,
+    noise: Option<usize>,
+    /// which buttons are currently held down, according to the button events
+    s1: bool,
+    s2: bool,
+    select: bool,
+    /// number of frames the joystick button has been held for
+    select_frames: u32,
+    /// joystick readings past these count as pushed
+    left_below: u16,
+    right_above: u16,
+    down_below: u16,
+    /// S1 rotates counterclockwise instead of clockwise (and S2 the other way)
+    swap_rotation: bool,
+}
+
+impl BoosterPack {
+    /// a joystick press shorter than this many frames is a tap, which (un)pauses the game
+    const TAP_FRAMES : u32 = 15;
+
+    /// `buttons::initialize` needs to have been called already
+    pub fn new(p: &tm4c123x::Peripherals, settings: &Settings) -> BoosterPack {
+        // the joystick reads 0 to 4095 on each axis, with the middle around 2048
+        let (left_below, right_above, down_below) =
+            match settings.joystick_sensitivity {
+                Sensitivity::Low => (20, 4060, 4),
+                Sensitivity::Medium => (50, 4000, 10),
+                Sensitivity::High => (600, 3500, 600),
+            };
+        BoosterPack {
+            snapshot: Default::default(),
+            noise: None,
+            s1: buttons::is_pressed(p, Button::S1),
+            s2: buttons::is_pressed(p, Button::S2),
+            select: buttons::is_pressed(p, Button::Select),
+            select_frames: 0,
+            left_below,
+            right_above,
+            down_below,
+            swap_rotation: settings.swap_rotation,
+        }
+    }
+
+    /// Hand over this frame's analog readings, before polling. main takes the snapshot every
+    /// frame even when the BoosterPack isn't in charge, the temperature is used for noise.
+    pub fn set_snapshot(&mut self, snapshot: AdcSnapshot) {
+        self.snapshot = snapshot;
+    }
+
+    /// The analog readings used for the last poll
+    pub fn snapshot(&self) -> AdcSnapshot {
+        self.snapshot
+    }
+}
+
+impl InputSource for BoosterPack {
+    fn poll(&mut self, input: &mut Input) -> Actions {
+        let snapshot = self.snapshot;
+
+        input.left = snapshot.joystick_x < self.left_below;
+        input.right = snapshot.joystick_x > self.right_above;
+        input.down = snapshot.joystick_y < self.down_below;
+
+        // use ADC readings to generate a "random" bit
+        self.noise = Some(((snapshot.joystick_x ^ snapshot.joystick_y) & 1) as usize);
+
+        // catch up on everything the buttons did since the last frame
+        let mut s1_pressed = false;
+        let mut s2_pressed = false;
+        let mut select_pressed = false;
+        while let Some(event) = buttons::next_event() {
+            match event.button {
+                Button::S1 => {
+                    self.s1 = event.pressed;
+                    s1_pressed |= event.pressed;
+                },
+                Button::S2 => {
+                    self.s2 = event.pressed;
+                    s2_pressed |= event.pressed;
+                },
+                Button::Select => {
+                    self.select = event.pressed;
+                    select_pressed |= event.pressed;
+                },
+            }
+        }
+
+        // a button that was pressed and let go within the same frame still counts for that frame
+        // button 1 (S1) is for clockwise rotation
+        // button 2 (S2) is for counter clockwise rotation
+        // (unless they've been swapped in the settings)
+        let s1 = self.s1 || s1_pressed;
+        let s2 = self.s2 || s2_pressed;
+        input.cw_rotate  = if self.swap_rotation { s2 } else { s1 };
+        input.ccw_rotate = if self.swap_rotation { s1 } else { s2 };
+
+        // the joystick button restarts when held and pauses when tapped
+        let select = self.select || select_pressed;
+
+        let tapped = !select && self.select_frames > 0 && self.select_frames <= BoosterPack::TAP_FRAMES;
+        self.select_frames = if select { self.select_frames.saturating_add(1) } else { 0 };
+
+        Actions {
+            pause: tapped,
+            restart: select,
+        }
+    }
+
+    fn noise_bit(&self) -> Option<usize> {
+        self.noise
+    }
+}
+
+/// Input sent over UART0, one byte per change (see the bit layout in input.rs).
+/// The last byte received stays in effect until a new one shows up.
+pub struct UartRemote<'a> {
+    uart: &'a Uart0<'a>,
+    state: u8,
+    previous: u8,
+    connected: bool,
+}
+
+impl<'a> UartRemote<'a> {
+    pub fn new(uart: &'a Uart0<'a>) -> UartRemote<'a> {
+        UartRemote {
+            uart,
+            state: 0,
+            previous: 0,
+            connected: false,
+        }
+    }
+
+    /// The remote takes over as soon as anything is sent over the UART
+    pub fn is_connected(&mut self) -> bool {
+        if !self.connected && self.uart.byte_available() {
+            self.connected = true;
+        }
+        self.connected
+    }
+}
+
+impl InputSource for UartRemote<'_> {
+    fn poll(&mut self, input: &mut Input) -> Actions {
+        self.previous = self.state;
+        // only the most recent byte matters
+        while let Some(byte) = self.uart.read_byte() {
+            self.state = byte;
+        }
+        decode(self.state, self.previous, input)
+    }
+}
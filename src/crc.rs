@@ -0,0 +1,43 @@
+// CRC-32 (the same one zip and Ethernet use), to tell whether what's in the EEPROM or flash is
+// something we wrote or just garbage
+
+const POLYNOMIAL : u32 = 0xEDB8_8320;
+
+/// CRC of a bunch of words, each one least significant byte first
+pub fn crc32(words: &[u32]) -> u32 {
+    let mut crc = Crc32::new();
+    for word in words {
+        crc.update(*word);
+    }
+    crc.finish()
+}
+
+/// The same thing a word at a time, for data that isn't all in one slice
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { crc: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, word: u32) {
+        for byte in word.to_le_bytes().iter() {
+            self.crc ^= *byte as u32;
+            for _ in 0..8 {
+                self.crc = if self.crc & 1 != 0 { (self.crc >> 1) ^ POLYNOMIAL } else { self.crc >> 1 };
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
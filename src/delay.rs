@@ -0,0 +1,54 @@
+use cortex_m::peripheral::SYST;
+use cortex_m::peripheral::syst::SystClkSource;
+
+use crate::clock::SYSTEM_CLOCK_HZ;
+
+// Busy-wait delays timed with SysTick, so they're the same length no matter how fast
+// the clock is or how well the compiler optimized the loop.
+//
+// SysTick is only 24 bits (about 0.2 s at 80 MHz), longer delays are done in chunks.
+
+/// largest SysTick reload value
+const MAX_TICKS : u32 = 0x00FF_FFFF;
+const TICKS_PER_US : u32 = SYSTEM_CLOCK_HZ / 1_000_000;
+
+pub struct Delay {
+    syst: SYST,
+}
+
+impl Delay {
+    pub fn new(mut syst: SYST) -> Delay {
+        syst.set_clock_source(SystClkSource::Core);
+        syst.disable_interrupt();
+        syst.disable_counter();
+        Delay {
+            syst,
+        }
+    }
+
+    /// Wait at least `us` microseconds
+    pub fn delay_us(&mut self, us: u32) {
+        let mut ticks = us as u64 * TICKS_PER_US as u64;
+        while ticks > 0 {
+            let chunk = if ticks > MAX_TICKS as u64 { MAX_TICKS } else { ticks as u32 };
+            self.wait_ticks(chunk);
+            ticks -= chunk as u64;
+        }
+    }
+
+    /// Wait at least `ms` milliseconds
+    pub fn delay_ms(&mut self, ms: u32) {
+        self.delay_us(ms.saturating_mul(1000));
+    }
+
+    fn wait_ticks(&mut self, ticks: u32) {
+        // the counter goes from the reload value down to 0, so it wraps after reload + 1 ticks
+        self.syst.set_reload(ticks - 1);
+        self.syst.clear_current();
+        // reading the control register clears COUNTFLAG, don't want a stale one from last time
+        let _ = self.syst.has_wrapped();
+        self.syst.enable_counter();
+        while !self.syst.has_wrapped() {}
+        self.syst.disable_counter();
+    }
+}
@@ -0,0 +1,70 @@
+// The TM4C123 has 2 KB of EEPROM built in: 32 blocks of 16 words each. Words are read and
+// written one at a time by picking a block (EEBLOCK) and a word in it (EEOFFSET), then using
+// EERDWR. Writes take a while (up to a few ms if the EEPROM has to erase a page), EEDONE says
+// when they're finished.
+//
+// Everything here is addressed in words, from 0 to WORDS - 1.
+//
+// This is just the layout, the driver is in eeprom_driver.rs since it needs the board. Anything
+// that saves itself goes through Storage, so the tests can use a pretend EEPROM in RAM.
+
+/// Total size, in 32 bit words
+pub const WORDS : usize = 512;
+
+// Where everything lives, in words
+/// the high score table (see highscore.rs), 32 words
+pub const HIGH_SCORES_ADDRESS : usize = 0;
+/// settings (see settings.rs), one block
+pub const SETTINGS_ADDRESS : usize = 32;
+pub const SETTINGS_WORDS : usize = 16;
+/// lifetime stats (see stats.rs), one block
+pub const STATS_ADDRESS : usize = 48;
+pub const STATS_WORDS : usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    /// an erase or copy failed, the EEPROM might be worn out
+    Retry,
+    /// the block is protected against writes
+    NoPermission,
+}
+
+/// Somewhere to keep words across resets
+pub trait Storage {
+    /// Read `words.len()` words starting at `address`
+    fn read(&self, address: usize, words: &mut [u32]);
+    /// Write `words` starting at `address`
+    fn write(&self, address: usize, words: &[u32]) -> Result<(), Error>;
+}
+
+/// An EEPROM in RAM for the tests, blank (all ones) to start with
+#[cfg(test)]
+pub struct Fake {
+    pub words: core::cell::RefCell<[u32; WORDS]>,
+}
+
+#[cfg(test)]
+impl Fake {
+    pub fn new() -> Fake {
+        Fake { words: core::cell::RefCell::new([0xFFFF_FFFF; WORDS]) }
+    }
+}
+
+#[cfg(test)]
+impl Default for Fake {
+    fn default() -> Fake {
+        Fake::new()
+    }
+}
+
+#[cfg(test)]
+impl Storage for Fake {
+    fn read(&self, address: usize, words: &mut [u32]) {
+        words.copy_from_slice(&self.words.borrow()[address..address + words.len()]);
+    }
+
+    fn write(&self, address: usize, words: &[u32]) -> Result<(), Error> {
+        self.words.borrow_mut()[address..address + words.len()].copy_from_slice(words);
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+// The driver for the EEPROM built into the TM4C123: 32 blocks of 16 words each. Words are read
+// and written one at a time by picking a block (EEBLOCK) and a word in it (EEOFFSET), then using
+// EERDWR. The layout of what's in there is in mcu_fourtris::eeprom (src/eeprom.rs).
+use mcu_fourtris::eeprom::{Error, Storage, WORDS};
+
+const WORDS_PER_BLOCK : usize = 16;
+
+// EEDONE bits
+const EEDONE_WORKING : u32 = 1;
+const EEDONE_NOPERM : u32 = 1 << 4;
+// EESUPP bits
+const EESUPP_ERETRY : u32 = 1 << 2;
+const EESUPP_PRETRY : u32 = 1 << 3;
+
+pub struct Eeprom<'a> {
+    p: &'a tm4c123x::Peripherals,
+}
+
+impl<'a> Eeprom<'a> {
+    /// Returns None if the EEPROM didn't come up properly (an erase was interrupted and
+    /// couldn't be finished), in which case nothing should be saved
+    pub fn new(p: &'a tm4c123x::Peripherals) -> Option<Eeprom<'a>> {
+        // 1. enable the clock for the EEPROM
+        p.SYSCTL.rcgceeprom.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        while p.SYSCTL.preeprom.read().bits() & 1 == 0 {}
+
+        let eeprom = Eeprom { p };
+        // 2. it might be finishing something from before the last reset
+        eeprom.wait();
+        // 3. if that didn't work out, don't touch it
+        if eeprom.retry_needed() {
+            return None;
+        }
+
+        // 4. reset the module, then wait for it to come back
+        p.SYSCTL.sreeprom.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        p.SYSCTL.sreeprom.modify(|r, w| unsafe { w.bits( r.bits() & !1 ) });
+        while p.SYSCTL.preeprom.read().bits() & 1 == 0 {}
+        eeprom.wait();
+        if eeprom.retry_needed() {
+            return None;
+        }
+        Some(eeprom)
+    }
+
+    /// Point EERDWR at a word
+    fn select(&self, address: usize) {
+        self.p.EEPROM.eeblock.write(|w| unsafe { w.bits( (address / WORDS_PER_BLOCK) as u32 ) });
+        self.p.EEPROM.eeoffset.write(|w| unsafe { w.bits( (address % WORDS_PER_BLOCK) as u32 ) });
+    }
+
+    /// Wait until the EEPROM isn't busy anymore, returns EEDONE
+    fn wait(&self) -> u32 {
+        loop {
+            let done = self.p.EEPROM.eedone.read().bits();
+            if done & EEDONE_WORKING == 0 {
+                return done;
+            }
+        }
+    }
+
+    fn retry_needed(&self) -> bool {
+        self.p.EEPROM.eesupp.read().bits() & (EESUPP_ERETRY | EESUPP_PRETRY) != 0
+    }
+}
+
+impl<'a> Storage for Eeprom<'a> {
+    fn read(&self, address: usize, words: &mut [u32]) {
+        assert!(address + words.len() <= WORDS);
+        for (i, word) in words.iter_mut().enumerate() {
+            self.select(address + i);
+            *word = self.p.EEPROM.eerdwr.read().bits();
+        }
+    }
+
+    /// Write `words` starting at `address`. Words that already hold the right value are
+    /// skipped, the EEPROM only lasts so many writes.
+    fn write(&self, address: usize, words: &[u32]) -> Result<(), Error> {
+        assert!(address + words.len() <= WORDS);
+        for (i, word) in words.iter().enumerate() {
+            self.select(address + i);
+            if self.p.EEPROM.eerdwr.read().bits() == *word {
+                continue;
+            }
+
+            self.p.EEPROM.eerdwr.write(|w| unsafe { w.bits(*word) });
+            let done = self.wait();
+            if done & EEDONE_NOPERM != 0 {
+                return Err(Error::NoPermission);
+            }
+            if self.retry_needed() {
+                return Err(Error::Retry);
+            }
+        }
+        Ok(())
+    }
+}
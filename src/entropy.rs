@@ -0,0 +1,397 @@
+// Checks the "random" bits we pick up from noise before they get anywhere near Randy.
+//
+// Every source runs the two continuous health tests from NIST SP 800-90B (section 4.4) on its
+// raw bits. Bits that make it through are debiased with a von Neumann extractor. Sources are
+// used in order of preference, the first healthy one wins:
+//   1. joystick   - LSB of the X and Y readings XORed together
+//   2. temperature - LSB of the internal temperature sensor reading
+//   3. timer jitter - LSB of Timer0 when we get around to sampling it. This one is weak,
+//                     everything runs off the same clock, but it's better than nothing.
+//
+// The test parameters assume each raw bit carries at least half a bit of entropy (H = 0.5)
+// and aim for a false positive rate of 2^-20 (alpha).
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    Joystick = 0,
+    Temperature = 1,
+    TimerJitter = 2,
+}
+
+const SOURCES : usize = 3;
+
+/// Repetition count test cutoff: 1 + ceil(-log2(alpha) / H) = 1 + 20 / 0.5
+const REPETITION_CUTOFF : u32 = 41;
+/// Adaptive proportion test window size for binary sources
+const PROPORTION_WINDOW : u32 = 1024;
+/// Adaptive proportion test cutoff: 1 + CRITBINOM(1024, 2^-0.5, 1 - 2^-20)
+const PROPORTION_CUTOFF : u32 = 793;
+
+/// SP 800-90B 4.4.1: fails if the same bit shows up too many times in a row
+struct RepetitionCount {
+    last: usize,
+    count: u32,
+}
+
+impl RepetitionCount {
+    fn new() -> RepetitionCount {
+        RepetitionCount {
+            last: 0,
+            count: 0,
+        }
+    }
+
+    /// returns false if the test failed
+    fn feed(&mut self, bit: usize) -> bool {
+        if self.count > 0 && bit == self.last {
+            self.count += 1;
+        } else {
+            self.last = bit;
+            self.count = 1;
+        }
+        self.count < REPETITION_CUTOFF
+    }
+}
+
+/// SP 800-90B 4.4.2: fails if the first bit of a window shows up too often in that window
+struct AdaptiveProportion {
+    first: usize,
+    matches: u32,
+    /// number of bits seen in the current window, 0 means we need a new window
+    seen: u32,
+}
+
+impl AdaptiveProportion {
+    fn new() -> AdaptiveProportion {
+        AdaptiveProportion {
+            first: 0,
+            matches: 0,
+            seen: 0,
+        }
+    }
+
+    /// returns false if the test failed
+    fn feed(&mut self, bit: usize) -> bool {
+        if self.seen == 0 {
+            self.first = bit;
+            self.matches = 1;
+            self.seen = 1;
+            return true;
+        }
+
+        if bit == self.first {
+            self.matches += 1;
+        }
+        self.seen += 1;
+        if self.seen == PROPORTION_WINDOW {
+            self.seen = 0;
+        }
+        self.matches < PROPORTION_CUTOFF
+    }
+
+    /// true when a full window has just been completed
+    fn window_done(&self) -> bool {
+        self.seen == 0
+    }
+}
+
+/// Looks at bits two at a time: 01 -> 0, 10 -> 1, 00 and 11 get thrown out.
+/// Removes bias as long as the bits are independent.
+struct VonNeumann {
+    first: Option<usize>,
+}
+
+impl VonNeumann {
+    fn feed(&mut self, bit: usize) -> Option<usize> {
+        match self.first.take() {
+            None => {
+                self.first = Some(bit);
+                None
+            },
+            Some(first) if first != bit => Some(first),
+            Some(_) => None,
+        }
+    }
+}
+
+struct SourceHealth {
+    repetition: RepetitionCount,
+    proportion: AdaptiveProportion,
+    debiaser: VonNeumann,
+    /// a failed source isn't used until it gets through a whole proportion window cleanly
+    failed: bool,
+    /// number of times the source has failed (handy in the debugger)
+    failures: u32,
+}
+
+impl SourceHealth {
+    fn new() -> SourceHealth {
+        SourceHealth {
+            repetition: RepetitionCount::new(),
+            proportion: AdaptiveProportion::new(),
+            debiaser: VonNeumann { first: None },
+            failed: false,
+            failures: 0,
+        }
+    }
+
+    /// Run the health tests, and return a debiased bit if the source is healthy and one is ready
+    fn feed(&mut self, raw_bit: usize) -> Option<usize> {
+        let raw_bit = raw_bit & 1;
+        let repetition_ok = self.repetition.feed(raw_bit);
+        let proportion_ok = self.proportion.feed(raw_bit);
+
+        if !(repetition_ok && proportion_ok) {
+            if !self.failed {
+                self.failures = self.failures.saturating_add(1);
+            }
+            self.failed = true;
+            // start over so the source gets a fair second chance
+            self.repetition = RepetitionCount::new();
+            self.proportion = AdaptiveProportion::new();
+            self.debiaser = VonNeumann { first: None };
+            return None;
+        }
+
+        if self.failed {
+            if self.proportion.window_done() {
+                self.failed = false;
+            }
+            return None;
+        }
+
+        self.debiaser.feed(raw_bit)
+    }
+}
+
+pub struct Entropy {
+    sources: [SourceHealth; SOURCES],
+}
+
+impl Entropy {
+    pub fn new() -> Entropy {
+        Entropy {
+            sources: [SourceHealth::new(), SourceHealth::new(), SourceHealth::new()],
+        }
+    }
+
+    /// Feed in one raw bit from each source (joystick is optional since it isn't always
+    /// being sampled). Returns a debiased bit from the most preferred healthy source that
+    /// was fed, if it has one ready.
+    pub fn harvest(&mut self, joystick: Option<usize>, temperature: usize, timer_jitter: usize) -> Option<usize> {
+        let raw = [joystick, Some(temperature), Some(timer_jitter)];
+
+        // every source gets fed so its health tests keep running
+        let mut debiased = [None; SOURCES];
+        for ((health, raw_bit), bit) in self.sources.iter_mut().zip(raw.iter()).zip(debiased.iter_mut()) {
+            if let Some(raw_bit) = raw_bit {
+                *bit = health.feed(*raw_bit);
+            }
+        }
+
+        self.sources.iter()
+            .zip(raw.iter())
+            .zip(debiased.iter())
+            .find(|((health, raw_bit), _)| raw_bit.is_some() && !health.failed)
+            .and_then(|(_, bit)| *bit)
+    }
+
+    /// The source bits are currently being taken from, None if every source has failed
+    pub fn preferred_source(&self) -> Option<Source> {
+        [Source::Joystick, Source::Temperature, Source::TimerJitter].iter()
+            .copied()
+            .find(|source| self.is_healthy(*source))
+    }
+
+    pub fn is_healthy(&self, source: Source) -> bool {
+        !self.sources[source as usize].failed
+    }
+}
+
+impl Default for Entropy {
+    fn default() -> Entropy {
+        Entropy::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift32, stands in for a noise source
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn bit(&mut self) -> usize {
+            (self.next() >> 31) as usize
+        }
+
+        /// 1 with probability `percent` / 100
+        fn biased_bit(&mut self, percent: u32) -> usize {
+            (self.next() % 100 < percent) as usize
+        }
+    }
+
+    #[test]
+    fn repetition_count_trips_at_the_cutoff() {
+        for &bit in &[0, 1] {
+            let mut test = RepetitionCount::new();
+            for _ in 0..REPETITION_CUTOFF - 1 {
+                assert!(test.feed(bit));
+            }
+            // the 41st in a row
+            assert!(!test.feed(bit));
+        }
+
+        // a different bit starts the count over
+        let mut test = RepetitionCount::new();
+        for _ in 0..REPETITION_CUTOFF - 1 {
+            assert!(test.feed(1));
+        }
+        assert!(test.feed(0));
+        for _ in 0..REPETITION_CUTOFF - 2 {
+            assert!(test.feed(0));
+        }
+        assert!(!test.feed(0));
+    }
+
+    #[test]
+    fn adaptive_proportion_trips_at_the_cutoff() {
+        // 7 out of every 8 bits match the first one, so the cutoff comes up before the window
+        // is over. Runs are short enough that the repetition count wouldn't mind.
+        let bit = |i: u32| if i % 8 != 7 { 1 } else { 0 };
+
+        // 792 out of 1024 is fine
+        let mut test = AdaptiveProportion::new();
+        let mut matches = 0;
+        for i in 0..PROPORTION_WINDOW {
+            let bit = if matches < PROPORTION_CUTOFF - 1 { bit(i) } else { 0 };
+            matches += bit as u32;
+            assert!(test.feed(bit), "failed at bit {}", i);
+        }
+        assert!(test.window_done());
+
+        // 793 isn't
+        let mut test = AdaptiveProportion::new();
+        let mut matches = 0;
+        let mut failed_at = None;
+        for i in 0..PROPORTION_WINDOW {
+            matches += bit(i) as u32;
+            if !test.feed(bit(i)) {
+                failed_at = Some(matches);
+                break;
+            }
+        }
+        assert_eq!(failed_at, Some(PROPORTION_CUTOFF));
+    }
+
+    #[test]
+    fn von_neumann_removes_bias() {
+        let mut noise = Noise(0xDEAD_BEEF);
+        let mut debiaser = VonNeumann { first: None };
+        let mut ones = 0;
+        let mut outputs = 0;
+        let mut raw_ones = 0;
+        for _ in 0..200_000 {
+            let bit = noise.biased_bit(80);
+            raw_ones += bit;
+            if let Some(bit) = debiaser.feed(bit) {
+                ones += bit;
+                outputs += 1;
+            }
+        }
+        // 80/20 going in
+        assert!((raw_ones as f64 / 200_000.0 - 0.8).abs() < 0.01);
+        // only the 01 and 10 pairs come out, 2 * 0.8 * 0.2 of the 100,000 pairs
+        assert!((outputs as f64 - 32_000.0).abs() < 1_000.0, "{} bits out", outputs);
+        // and they're 50/50 (a 1% miss is about 3.5 standard deviations)
+        let ratio = ones as f64 / outputs as f64;
+        assert!((ratio - 0.5).abs() < 0.01, "{} ones out of {}", ones, outputs);
+    }
+
+    #[test]
+    fn healthy_sources_pass() {
+        let mut entropy = Entropy::new();
+        let mut noise = Noise(12345);
+        let mut bits = 0;
+        for _ in 0..100_000 {
+            if entropy.harvest(Some(noise.bit()), noise.bit(), noise.bit()).is_some() {
+                bits += 1;
+            }
+        }
+        assert!(bits > 20_000);
+        assert!(entropy.preferred_source() == Some(Source::Joystick));
+        assert!(entropy.is_healthy(Source::Temperature) && entropy.is_healthy(Source::TimerJitter));
+    }
+
+    #[test]
+    fn falls_back_to_the_next_source() {
+        // the same temperature and jitter bits go to a second Entropy that never gets any
+        // joystick bits, once the joystick fails both should hand out the same bits
+        let mut entropy = Entropy::new();
+        let mut without_joystick = Entropy::new();
+        let mut temperature = Noise(4242);
+        let mut jitter = Noise(99);
+
+        // the joystick gets stuck, everything else is fine
+        for _ in 0..REPETITION_CUTOFF {
+            let (t, j) = (temperature.bit(), jitter.bit());
+            entropy.harvest(Some(1), t, j);
+            without_joystick.harvest(None, t, j);
+        }
+        assert!(!entropy.is_healthy(Source::Joystick));
+        assert!(entropy.preferred_source() == Some(Source::Temperature));
+
+        let mut bits = 0;
+        for _ in 0..1000 {
+            let (t, j) = (temperature.bit(), jitter.bit());
+            let bit = entropy.harvest(Some(1), t, j);
+            assert_eq!(bit, without_joystick.harvest(None, t, j));
+            bits += bit.is_some() as u32;
+        }
+        assert!(bits > 100);
+
+        // then the temperature sensor gets stuck too
+        for _ in 0..REPETITION_CUTOFF {
+            entropy.harvest(Some(1), 0, jitter.bit());
+        }
+        assert!(entropy.preferred_source() == Some(Source::TimerJitter));
+        let mut bits = 0;
+        for _ in 0..1000 {
+            bits += entropy.harvest(Some(1), 0, jitter.bit()).is_some() as u32;
+        }
+        assert!(bits > 100);
+
+        // and the jitter: nothing left
+        for _ in 0..REPETITION_CUTOFF {
+            entropy.harvest(Some(1), 0, 1);
+        }
+        assert!(entropy.preferred_source().is_none());
+        assert_eq!(entropy.harvest(Some(1), 0, 1), None);
+    }
+
+    #[test]
+    fn failed_sources_come_back_after_a_clean_window() {
+        let mut entropy = Entropy::new();
+        let mut noise = Noise(31337);
+        for _ in 0..REPETITION_CUTOFF {
+            entropy.harvest(Some(0), noise.bit(), noise.bit());
+        }
+        assert!(entropy.preferred_source() == Some(Source::Temperature));
+
+        // a whole window of good bits
+        for _ in 0..PROPORTION_WINDOW - 1 {
+            entropy.harvest(Some(noise.bit()), noise.bit(), noise.bit());
+            assert!(!entropy.is_healthy(Source::Joystick));
+        }
+        entropy.harvest(Some(noise.bit()), noise.bit(), noise.bit());
+        assert!(entropy.preferred_source() == Some(Source::Joystick));
+    }
+}
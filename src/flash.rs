@@ -0,0 +1,82 @@
+// Writing to the on-chip flash, for things too big for the EEPROM.
+//
+// Flash is erased 1 KB at a time, which sets every bit to 1. After that each word can be written
+// once (writing can only change 1s to 0s). The CPU stalls while the flash is busy, so erasing and
+// writing block everything for a while: roughly 10-20 ms per page erased and 50 us per word.
+//
+// Only the region that memory.x keeps the program out of is ever touched. The driver is in
+// flash_driver.rs since it needs the board, the game log (suspend.rs) only sees it through
+// Storage, so the tests can use a pretend flash in RAM.
+
+/// Start of the region set aside in memory.x, the last 64 KB of flash
+pub const RESERVED_START : u32 = 0x0003_0000;
+pub const RESERVED_END : u32 = 0x0004_0000;
+pub const PAGE_SIZE : u32 = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    /// the flash controller reported a problem, the page might be worn out
+    Failed,
+}
+
+/// Somewhere to keep the saved game, addressed in bytes from RESERVED_START to RESERVED_END
+pub trait Storage {
+    fn read(&self, address: u32) -> u32;
+    /// Erase the page that `address` is in
+    fn erase_page(&self, address: u32) -> Result<(), Error>;
+    /// Write one word, it has to have been erased since the last time it was written
+    fn write(&self, address: u32, word: u32) -> Result<(), Error>;
+}
+
+/// Flash in RAM for the tests. It starts out holding junk, like flash that was written before.
+#[cfg(test)]
+pub struct Fake {
+    pub words: core::cell::RefCell<Vec<u32>>,
+}
+
+#[cfg(test)]
+impl Fake {
+    pub fn new() -> Fake {
+        Fake { words: core::cell::RefCell::new(vec![0x1234_5678; ((RESERVED_END - RESERVED_START) / 4) as usize]) }
+    }
+
+    fn index(address: u32) -> usize {
+        assert!((RESERVED_START..RESERVED_END).contains(&address) && address & 3 == 0);
+        ((address - RESERVED_START) / 4) as usize
+    }
+
+    /// Change a word behind the log's back, like a write that got cut off
+    pub fn set(&self, address: u32, word: u32) {
+        self.words.borrow_mut()[Fake::index(address)] = word;
+    }
+}
+
+#[cfg(test)]
+impl Default for Fake {
+    fn default() -> Fake {
+        Fake::new()
+    }
+}
+
+#[cfg(test)]
+impl Storage for Fake {
+    fn read(&self, address: u32) -> u32 {
+        self.words.borrow()[Fake::index(address)]
+    }
+
+    fn erase_page(&self, address: u32) -> Result<(), Error> {
+        let start = Fake::index(address & !(PAGE_SIZE - 1));
+        for word in &mut self.words.borrow_mut()[start..start + PAGE_SIZE as usize / 4] {
+            *word = 0xFFFF_FFFF;
+        }
+        Ok(())
+    }
+
+    fn write(&self, address: u32, word: u32) -> Result<(), Error> {
+        let mut words = self.words.borrow_mut();
+        let index = Fake::index(address);
+        assert_eq!(words[index], 0xFFFF_FFFF, "{:#x} written without being erased", address);
+        words[index] = word;
+        Ok(())
+    }
+}
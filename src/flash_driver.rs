@@ -0,0 +1,64 @@
+// The driver for the on-chip flash, see mcu_fourtris::flash (src/flash.rs) for how it works and
+// what it's used for.
+use mcu_fourtris::flash::{Error, Storage, PAGE_SIZE, RESERVED_END, RESERVED_START};
+
+// FMC bits
+const FMC_WRITE : u32 = 1;
+const FMC_ERASE : u32 = 1 << 1;
+const FMC_WRKEY_SHIFT : u32 = 16;
+/// BOOTCFG KEY: which write key the flash wants
+const BOOTCFG_KEY : u32 = 1 << 4;
+// FCRIS bits: access violation, voltage problem, invalid data, erase and program verify errors
+const FCRIS_ERRORS : u32 = 1 | (1 << 9) | (1 << 10) | (1 << 11) | (1 << 13);
+
+pub struct Flash<'a> {
+    p: &'a tm4c123x::Peripherals,
+    /// goes in the top half of FMC, or nothing happens
+    key: u32,
+}
+
+impl<'a> Flash<'a> {
+    pub fn new(p: &'a tm4c123x::Peripherals) -> Flash<'a> {
+        let key = if p.FLASH_CTRL.bootcfg.read().bits() & BOOTCFG_KEY != 0 { 0xA442 } else { 0x71D5 };
+        Flash { p, key }
+    }
+
+    /// Did the last erase or write work? Clears the error for next time.
+    fn check(&self) -> Result<(), Error> {
+        let errors = self.p.FLASH_CTRL.fcris.read().bits() & FCRIS_ERRORS;
+        if errors == 0 {
+            return Ok(());
+        }
+        // the masked interrupt status register clears the raw status bits
+        self.p.FLASH_CTRL.fcmisc.write(|w| unsafe { w.bits(errors) });
+        Err(Error::Failed)
+    }
+}
+
+impl Storage for Flash<'_> {
+    fn read(&self, address: u32) -> u32 {
+        assert!(address >= RESERVED_START && address < RESERVED_END && address % 4 == 0);
+        unsafe { core::ptr::read_volatile(address as *const u32) }
+    }
+
+    fn erase_page(&self, address: u32) -> Result<(), Error> {
+        assert!(address >= RESERVED_START && address < RESERVED_END);
+        // 1. address of the page
+        self.p.FLASH_CTRL.fma.write(|w| unsafe { w.bits( address & !(PAGE_SIZE - 1) ) });
+        // 2. start erasing, and wait for it to finish
+        self.p.FLASH_CTRL.fmc.write(|w| unsafe { w.bits( (self.key << FMC_WRKEY_SHIFT) | FMC_ERASE ) });
+        while self.p.FLASH_CTRL.fmc.read().bits() & FMC_ERASE != 0 {}
+        self.check()
+    }
+
+    fn write(&self, address: u32, word: u32) -> Result<(), Error> {
+        assert!(address >= RESERVED_START && address < RESERVED_END && address % 4 == 0);
+        // 1. address and data
+        self.p.FLASH_CTRL.fma.write(|w| unsafe { w.bits(address) });
+        self.p.FLASH_CTRL.fmd.write(|w| unsafe { w.bits(word) });
+        // 2. start writing, and wait for it to finish
+        self.p.FLASH_CTRL.fmc.write(|w| unsafe { w.bits( (self.key << FMC_WRKEY_SHIFT) | FMC_WRITE ) });
+        while self.p.FLASH_CTRL.fmc.read().bits() & FMC_WRITE != 0 {}
+        self.check()
+    }
+}
@@ -0,0 +1,107 @@
+// A tiny font to go along with the LEVEL and SCORE text: every character is 4 pixels wide
+// and 8 pixels tall. Each row is 4 bits, the most significant bit is the leftmost pixel.
+// The digits match NUMBER_TEXT in lcd_backend.rs.
+
+pub const GLYPH_WIDTH : u8 = 4;
+pub const GLYPH_HEIGHT : u8 = 8;
+
+pub type Glyph = [u8; 8];
+
+const BLANK : Glyph = [0; 8];
+
+const LETTERS : [Glyph; 26] = [
+    // A
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001],
+    // B
+    [0b1110, 0b1001, 0b1001, 0b1110, 0b1001, 0b1001, 0b1001, 0b1110],
+    // C
+    [0b0110, 0b1001, 0b1000, 0b1000, 0b1000, 0b1000, 0b1001, 0b0110],
+    // D
+    [0b1110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1110],
+    // E
+    [0b1111, 0b1000, 0b1000, 0b1110, 0b1000, 0b1000, 0b1000, 0b1111],
+    // F
+    [0b1111, 0b1000, 0b1000, 0b1110, 0b1000, 0b1000, 0b1000, 0b1000],
+    // G
+    [0b0110, 0b1001, 0b1000, 0b1000, 0b1011, 0b1001, 0b1001, 0b0111],
+    // H
+    [0b1001, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001, 0b1001],
+    // I
+    [0b1110, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100, 0b1110],
+    // J
+    [0b0011, 0b0001, 0b0001, 0b0001, 0b0001, 0b1001, 0b1001, 0b0110],
+    // K
+    [0b1001, 0b1001, 0b1010, 0b1100, 0b1010, 0b1001, 0b1001, 0b1001],
+    // L
+    [0b1000, 0b1000, 0b1000, 0b1000, 0b1000, 0b1000, 0b1000, 0b1111],
+    // M
+    [0b1001, 0b1111, 0b1111, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001],
+    // N
+    [0b1001, 0b1101, 0b1101, 0b1011, 0b1011, 0b1001, 0b1001, 0b1001],
+    // O
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110],
+    // P
+    [0b1110, 0b1001, 0b1001, 0b1110, 0b1000, 0b1000, 0b1000, 0b1000],
+    // Q
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1011, 0b0110, 0b0001],
+    // R
+    [0b1110, 0b1001, 0b1001, 0b1110, 0b1010, 0b1001, 0b1001, 0b1001],
+    // S
+    [0b0110, 0b1001, 0b1000, 0b0110, 0b0001, 0b0001, 0b1001, 0b0110],
+    // T
+    [0b1110, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100],
+    // U
+    [0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110],
+    // V
+    [0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110, 0b0110],
+    // W
+    [0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1111, 0b1111, 0b1001],
+    // X
+    [0b1001, 0b1001, 0b1001, 0b0110, 0b0110, 0b1001, 0b1001, 0b1001],
+    // Y
+    [0b1010, 0b1010, 0b1010, 0b0100, 0b0100, 0b0100, 0b0100, 0b0100],
+    // Z
+    [0b1111, 0b0001, 0b0010, 0b0010, 0b0100, 0b0100, 0b1000, 0b1111],
+];
+
+const DIGITS : [Glyph; 10] = [
+    // 0
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110],
+    // 1
+    [0b0010, 0b0110, 0b0010, 0b0010, 0b0010, 0b0010, 0b0010, 0b0111],
+    // 2
+    [0b0110, 0b1001, 0b0001, 0b0001, 0b0110, 0b1000, 0b1000, 0b1111],
+    // 3
+    [0b0110, 0b1001, 0b0001, 0b0001, 0b0010, 0b0001, 0b1001, 0b0110],
+    // 4
+    [0b0010, 0b1010, 0b1010, 0b1010, 0b1111, 0b0010, 0b0010, 0b0010],
+    // 5
+    [0b1111, 0b1000, 0b1000, 0b1000, 0b0110, 0b0001, 0b1001, 0b0110],
+    // 6
+    [0b0110, 0b1001, 0b1000, 0b1110, 0b1001, 0b1001, 0b1001, 0b0110],
+    // 7
+    [0b1111, 0b0001, 0b0001, 0b0010, 0b0100, 0b0100, 0b0100, 0b0100],
+    // 8
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b0110, 0b1001, 0b1001, 0b0110],
+    // 9
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b0111, 0b0001, 0b1001, 0b0110],
+];
+
+const DASH  : Glyph = [0b0000, 0b0000, 0b0000, 0b1111, 0b0000, 0b0000, 0b0000, 0b0000];
+const COLON : Glyph = [0b0000, 0b0000, 0b0100, 0b0000, 0b0000, 0b0100, 0b0000, 0b0000];
+const DOT   : Glyph = [0b0000, 0b0000, 0b0000, 0b0000, 0b0000, 0b0000, 0b0000, 0b0100];
+const ARROW : Glyph = [0b1000, 0b0100, 0b0010, 0b0001, 0b0010, 0b0100, 0b1000, 0b0000];
+
+/// Look up the glyph for an ASCII character, anything we don't have a glyph for is blank
+pub fn glyph(c: u8) -> &'static Glyph {
+    match c {
+        b'A'..=b'Z' => &LETTERS[(c - b'A') as usize],
+        b'a'..=b'z' => &LETTERS[(c - b'a') as usize],
+        b'0'..=b'9' => &DIGITS[(c - b'0') as usize],
+        b'-' => &DASH,
+        b':' => &COLON,
+        b'.' => &DOT,
+        b'>' => &ARROW,
+        _ => &BLANK,
+    }
+}
@@ -0,0 +1,305 @@
+use fourtris::game::Input;
+
+use crate::crc;
+use crate::eeprom::{self, Storage};
+
+// The top 10 scores, kept in the EEPROM so they survive a reset.
+//
+// In the EEPROM the table takes up RECORD_WORDS words:
+//   word 0          VERSION, so a table from some other layout is never read back as garbage
+//   words 1 - 30    the entries, 3 words each (see Entry::to_words), highest score first
+//   word 31         CRC of words 0 - 30
+// A missing entry is all zeros. If the version or CRC doesn't match, the table starts out empty.
+
+pub const ENTRIES : usize = 10;
+pub const INITIALS : usize = 3;
+
+/// "HS" and the layout version
+const VERSION : u32 = 0x4853_0001;
+const ENTRY_WORDS : usize = 3;
+const RECORD_WORDS : usize = 1 + ENTRIES * ENTRY_WORDS + 1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Entry {
+    /// uppercase ASCII letters
+    pub initials: [u8; INITIALS],
+    pub score: u32,
+    pub level: u8,
+    pub lines: u32,
+}
+
+impl Entry {
+    const EMPTY : Entry = Entry { initials: [0; INITIALS], score: 0, level: 0, lines: 0 };
+
+    // word 0: initials in the bottom 3 bytes, level in the top one
+    // word 1: score
+    // word 2: lines
+    fn to_words(self) -> [u32; ENTRY_WORDS] {
+        [self.initials[0] as u32
+            | (self.initials[1] as u32) << 8
+            | (self.initials[2] as u32) << 16
+            | (self.level as u32) << 24,
+         self.score,
+         self.lines]
+    }
+
+    fn from_words(words: &[u32]) -> Entry {
+        Entry {
+            initials: [words[0] as u8, (words[0] >> 8) as u8, (words[0] >> 16) as u8],
+            level: (words[0] >> 24) as u8,
+            score: words[1],
+            lines: words[2],
+        }
+    }
+}
+
+pub struct HighScores {
+    entries: [Entry; ENTRIES],
+    /// number of entries that are filled in
+    count: usize,
+}
+
+impl HighScores {
+    pub fn new() -> HighScores {
+        HighScores {
+            entries: [Entry::EMPTY; ENTRIES],
+            count: 0,
+        }
+    }
+
+    /// Read the table out of the EEPROM, or start a new one if there isn't a good one there
+    pub fn load(eeprom: &impl Storage) -> HighScores {
+        let mut words = [0; RECORD_WORDS];
+        eeprom.read(eeprom::HIGH_SCORES_ADDRESS, &mut words);
+        HighScores::from_words(&words).unwrap_or_default()
+    }
+
+    pub fn save(&self, eeprom: &impl Storage) -> Result<(), eeprom::Error> {
+        eeprom.write(eeprom::HIGH_SCORES_ADDRESS, &self.to_words())
+    }
+
+    /// The filled in entries, highest score first
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..self.count]
+    }
+
+    /// Would `score` make it onto the table?
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.count < ENTRIES || score > self.entries[ENTRIES - 1].score)
+    }
+
+    /// Put `entry` where it belongs, the lowest score falls off the end if the table is full.
+    /// Returns where it ended up, or None if it didn't make the cut.
+    /// A tie goes to whoever got there first.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self.entries[..self.count].iter()
+                       .position(|e| entry.score > e.score)
+                       .unwrap_or(self.count);
+        if self.count < ENTRIES {
+            self.count += 1;
+        }
+        for i in (rank + 1..self.count).rev() {
+            self.entries[i] = self.entries[i - 1];
+        }
+        self.entries[rank] = entry;
+        Some(rank)
+    }
+
+    fn to_words(&self) -> [u32; RECORD_WORDS] {
+        let mut words = [0; RECORD_WORDS];
+        words[0] = VERSION;
+        for (i, entry) in self.entries().iter().enumerate() {
+            let start = 1 + i * ENTRY_WORDS;
+            words[start..start + ENTRY_WORDS].copy_from_slice(&entry.to_words());
+        }
+        words[RECORD_WORDS - 1] = crc::crc32(&words[..RECORD_WORDS - 1]);
+        words
+    }
+
+    fn from_words(words: &[u32; RECORD_WORDS]) -> Option<HighScores> {
+        if words[0] != VERSION || words[RECORD_WORDS - 1] != crc::crc32(&words[..RECORD_WORDS - 1]) {
+            return None;
+        }
+        let mut high_scores = HighScores::new();
+        for chunk in words[1..RECORD_WORDS - 1].chunks(ENTRY_WORDS) {
+            let entry = Entry::from_words(chunk);
+            // the entries are packed at the front, the first empty one is the end
+            if entry.initials[0] == 0 {
+                break;
+            }
+            high_scores.entries[high_scores.count] = entry;
+            high_scores.count += 1;
+        }
+        Some(high_scores)
+    }
+}
+
+impl Default for HighScores {
+    fn default() -> HighScores {
+        HighScores::new()
+    }
+}
+
+/// What the initials entry wants after handling a frame of input
+#[derive(Clone, Copy, PartialEq)]
+pub enum EntryStatus {
+    Unchanged,
+    /// a letter or the cursor changed and needs to be redrawn
+    Changed,
+    /// all three letters are in
+    Done,
+}
+
+/// Typing in initials with the joystick, one letter at a time
+///  - joystick left/right: previous/next letter
+///  - joystick down or the joystick button: on to the next letter
+pub struct InitialsEntry {
+    initials: [u8; INITIALS],
+    /// which letter is being edited
+    cursor: usize,
+}
+
+impl InitialsEntry {
+    /// Start from `initials`, whoever got the last high score is likely to get the next one
+    pub fn new(initials: [u8; INITIALS]) -> InitialsEntry {
+        InitialsEntry {
+            initials,
+            cursor: 0,
+        }
+    }
+
+    pub fn initials(&self) -> [u8; INITIALS] {
+        self.initials
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// `pressed` should only have what was pressed this frame (see input::Presses),
+    /// `next` is the joystick button being tapped
+    pub fn update(&mut self, pressed: &Input, next: bool) -> EntryStatus {
+        let letter = &mut self.initials[self.cursor];
+        if pressed.down || next {
+            self.cursor += 1;
+            if self.cursor == INITIALS {
+                return EntryStatus::Done;
+            }
+        } else if pressed.right {
+            *letter = if *letter >= b'Z' { b'A' } else { *letter + 1 };
+        } else if pressed.left {
+            *letter = if *letter <= b'A' { b'Z' } else { *letter - 1 };
+        } else {
+            return EntryStatus::Unchanged;
+        }
+        EntryStatus::Changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &[u8; INITIALS], score: u32) -> Entry {
+        Entry { initials: *initials, score, level: (score / 1000) as u8, lines: score / 100 }
+    }
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores.entries().iter().map(|e| e.score).collect()
+    }
+
+    /// A full table: 1000, 900, ... 100
+    fn full() -> HighScores {
+        let mut high_scores = HighScores::new();
+        for i in 1..=ENTRIES as u32 {
+            high_scores.insert(entry(b"AAA", i * 100));
+        }
+        high_scores
+    }
+
+    #[test]
+    fn insert_keeps_the_highest_first() {
+        let mut high_scores = HighScores::new();
+        assert_eq!(high_scores.insert(entry(b"AAA", 500)), Some(0));
+        assert_eq!(high_scores.insert(entry(b"BBB", 700)), Some(0));
+        assert_eq!(high_scores.insert(entry(b"CCC", 100)), Some(2));
+        assert_eq!(high_scores.insert(entry(b"DDD", 600)), Some(1));
+        assert_eq!(scores(&high_scores), [700, 600, 500, 100]);
+        // a score of 0 isn't worth remembering
+        assert!(!high_scores.qualifies(0));
+        assert_eq!(high_scores.insert(entry(b"EEE", 0)), None);
+    }
+
+    #[test]
+    fn ties_go_to_whoever_got_there_first() {
+        let mut high_scores = HighScores::new();
+        high_scores.insert(entry(b"AAA", 500));
+        high_scores.insert(entry(b"CCC", 300));
+        assert_eq!(high_scores.insert(entry(b"BBB", 500)), Some(1));
+        assert_eq!(high_scores.insert(entry(b"DDD", 300)), Some(3));
+        let initials : Vec<[u8; INITIALS]> = high_scores.entries().iter().map(|e| e.initials).collect();
+        assert_eq!(initials, [*b"AAA", *b"BBB", *b"CCC", *b"DDD"]);
+    }
+
+    #[test]
+    fn a_full_table_drops_the_lowest() {
+        let mut high_scores = full();
+        assert_eq!(high_scores.entries().len(), ENTRIES);
+
+        // lower than everything, or tied with the lowest: no room
+        assert!(!high_scores.qualifies(50));
+        assert_eq!(high_scores.insert(entry(b"BBB", 50)), None);
+        assert_eq!(high_scores.insert(entry(b"BBB", 100)), None);
+
+        assert_eq!(high_scores.insert(entry(b"BBB", 550)), Some(5));
+        assert_eq!(scores(&high_scores), [1000, 900, 800, 700, 600, 550, 500, 400, 300, 200]);
+        assert_eq!(high_scores.insert(entry(b"CCC", 5000)), Some(0));
+        assert_eq!(scores(&high_scores), [5000, 1000, 900, 800, 700, 600, 550, 500, 400, 300]);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        for high_scores in [HighScores::new(), full()] {
+            let mut high_scores = high_scores;
+            high_scores.insert(entry(b"XYZ", 12345));
+            let read = HighScores::from_words(&high_scores.to_words()).unwrap();
+            assert!(read.entries() == high_scores.entries());
+        }
+        let read = HighScores::from_words(&HighScores::new().to_words()).unwrap();
+        assert!(read.entries().is_empty());
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let eeprom = eeprom::Fake::new();
+        // nothing saved yet
+        assert!(HighScores::load(&eeprom).entries().is_empty());
+        full().save(&eeprom).unwrap();
+        assert!(HighScores::load(&eeprom).entries() == full().entries());
+    }
+
+    #[test]
+    fn bad_records_are_ignored() {
+        let words = full().to_words();
+
+        // blank EEPROM
+        assert!(HighScores::from_words(&[0xFFFF_FFFF; RECORD_WORDS]).is_none());
+        assert!(HighScores::from_words(&[0; RECORD_WORDS]).is_none());
+
+        // any flipped bit
+        for i in 0..RECORD_WORDS {
+            let mut corrupt = words;
+            corrupt[i] ^= 0x100;
+            assert!(HighScores::from_words(&corrupt).is_none(), "word {}", i);
+        }
+
+        // another layout, even with a good CRC
+        let mut other = words;
+        other[0] = VERSION + 1;
+        other[RECORD_WORDS - 1] = crc::crc32(&other[..RECORD_WORDS - 1]);
+        assert!(HighScores::from_words(&other).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use fourtris::game::Input;
+
+// The BoosterPack and the UART remote need the hardware, so they're in controls.rs
+
+/// Things the player can ask for that aren't part of the game itself
+#[derive(Clone, Copy, Default)]
+pub struct Actions {
+    /// toggle pause, this is only set on the frame it was requested
+    pub pause: bool,
+    /// the restart button is being held down, RestartHold decides when to actually restart
+    pub restart: bool,
+}
+
+/// Anything that can drive the game: the BoosterPack, a remote over UART, a recording...
+pub trait InputSource {
+    /// Update `input` for this frame and report any meta actions
+    fn poll(&mut self, input: &mut Input) -> Actions;
+
+    /// A "random" bit picked up from noise while polling, if the source has any
+    fn noise_bit(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Remotes and recordings describe one frame of input as a single byte
+//   bit:   6      5        4          3         2      1       0
+//        PAUSE RESTART CCW_ROTATE CW_ROTATE   DOWN   RIGHT   LEFT
+const LEFT_BIT       : u8 = 0x01;
+const RIGHT_BIT      : u8 = 0x02;
+const DOWN_BIT       : u8 = 0x04;
+const CW_ROTATE_BIT  : u8 = 0x08;
+const CCW_ROTATE_BIT : u8 = 0x10;
+const RESTART_BIT    : u8 = 0x20;
+const PAUSE_BIT      : u8 = 0x40;
+
+/// Decode a frame byte. `previous` is the byte from the last frame, which is needed
+/// because pause only triggers when its bit goes from 0 to 1.
+pub fn decode(bits: u8, previous: u8, input: &mut Input) -> Actions {
+    input.left = bits & LEFT_BIT != 0;
+    input.right = bits & RIGHT_BIT != 0;
+    input.down = bits & DOWN_BIT != 0;
+    input.cw_rotate = bits & CW_ROTATE_BIT != 0;
+    input.ccw_rotate = bits & CCW_ROTATE_BIT != 0;
+
+    Actions {
+        pause: bits & PAUSE_BIT != 0 && previous & PAUSE_BIT == 0,
+        restart: bits & RESTART_BIT != 0,
+    }
+}
+
+/// Describe `input` as a frame byte, without any actions
+pub fn encode(input: &Input) -> u8 {
+    let mut bits = 0;
+    if input.left { bits |= LEFT_BIT; }
+    if input.right { bits |= RIGHT_BIT; }
+    if input.down { bits |= DOWN_BIT; }
+    if input.cw_rotate { bits |= CW_ROTATE_BIT; }
+    if input.ccw_rotate { bits |= CCW_ROTATE_BIT; }
+    bits
+}
+
+/// The Input that `encode` turned into `bits`
+pub fn decode_input(bits: u8) -> Input {
+    let mut input = Default::default();
+    decode(bits, bits, &mut input);
+    input
+}
+
+/// True if anything the game uses is being held down
+pub fn anything_held(input: &Input) -> bool {
+    encode(input) != 0
+}
+
+/// Turns held buttons into presses, for menus and such where holding a direction
+/// shouldn't keep doing something every frame
+#[derive(Default)]
+pub struct Presses {
+    previous: u8,
+}
+
+impl Presses {
+    /// Call once per frame, returns what is down now but wasn't on the last call
+    pub fn update(&mut self, input: &Input) -> Input {
+        let bits = encode(input);
+        let mut pressed : Input = Default::default();
+        decode(bits & !self.previous, 0, &mut pressed);
+        self.previous = bits;
+        pressed
+    }
+}
+
+/// Plays back a recording, one byte per frame (see the bit layout above).
+/// Once the recording runs out, nothing is pressed.
+pub struct Replay<'a> {
+    script: &'a [u8],
+    position: usize,
+    previous: u8,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(script: &'a [u8]) -> Replay<'a> {
+        Replay {
+            script,
+            position: 0,
+            previous: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.script.len()
+    }
+}
+
+impl InputSource for Replay<'_> {
+    fn poll(&mut self, input: &mut Input) -> Actions {
+        let bits = match self.script.get(self.position) {
+            Some(bits) => {
+                self.position += 1;
+                *bits
+            },
+            None => 0,
+        };
+
+        let actions = decode(bits, self.previous, input);
+        self.previous = bits;
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fourtris::game::{Game, GameState};
+    use crate::randy::Randy;
+    use crate::testing;
+
+    /// Play `script` through a Replay, returning what each frame decoded to
+    fn play(script: &[u8], frames: usize) -> (Vec<u8>, Vec<Actions>) {
+        let mut replay = Replay::new(script);
+        let mut input : Input = Default::default();
+        let mut inputs = Vec::new();
+        let mut actions = Vec::new();
+        for _ in 0..frames {
+            actions.push(replay.poll(&mut input));
+            inputs.push(encode(&input));
+        }
+        (inputs, actions)
+    }
+
+    #[test]
+    fn encode_and_decode_agree() {
+        for bits in 0..0x20 {
+            assert_eq!(encode(&decode_input(bits)), bits);
+        }
+        // actions aren't part of the game's input
+        assert_eq!(encode(&decode_input(PAUSE_BIT | RESTART_BIT | LEFT_BIT)), LEFT_BIT);
+    }
+
+    #[test]
+    fn replay_plays_the_script() {
+        let script = [LEFT_BIT, LEFT_BIT | DOWN_BIT, CW_ROTATE_BIT, 0, RIGHT_BIT | CCW_ROTATE_BIT];
+        let (inputs, actions) = play(&script, script.len());
+        assert_eq!(inputs, script);
+        assert!(actions.iter().all(|actions| !actions.pause && !actions.restart));
+    }
+
+    #[test]
+    fn replay_pauses_on_the_first_frame_only() {
+        let script = [PAUSE_BIT, PAUSE_BIT, PAUSE_BIT | DOWN_BIT, 0, PAUSE_BIT, RESTART_BIT];
+        let (inputs, actions) = play(&script, script.len());
+        let pauses : Vec<bool> = actions.iter().map(|actions| actions.pause).collect();
+        assert_eq!(pauses, [true, false, false, false, true, false]);
+        let restarts : Vec<bool> = actions.iter().map(|actions| actions.restart).collect();
+        assert_eq!(restarts, [false, false, false, false, false, true]);
+        assert_eq!(inputs, [0, 0, DOWN_BIT, 0, 0, 0]);
+    }
+
+    #[test]
+    fn replay_lets_go_when_it_runs_out() {
+        let script = [LEFT_BIT | DOWN_BIT | RESTART_BIT];
+        let mut replay = Replay::new(&script);
+        let mut input : Input = Default::default();
+        assert!(!replay.is_finished());
+        assert!(replay.poll(&mut input).restart);
+        assert!(replay.is_finished());
+        for _ in 0..3 {
+            let actions = replay.poll(&mut input);
+            assert_eq!(encode(&input), 0);
+            assert!(!actions.pause && !actions.restart);
+        }
+    }
+
+    #[test]
+    fn presses_only_count_once() {
+        let mut presses = Presses::default();
+        let frames = [LEFT_BIT, LEFT_BIT, LEFT_BIT | CW_ROTATE_BIT, 0, LEFT_BIT];
+        let pressed : Vec<u8> = frames.iter()
+                                    .map(|bits| encode(&presses.update(&decode_input(*bits))))
+                                    .collect();
+        assert_eq!(pressed, [LEFT_BIT, 0, CW_ROTATE_BIT, 0, LEFT_BIT]);
+    }
+
+    /// Play a game from `script` through a Replay, with the pieces from a seeded Randy
+    fn replay_game(script: &[u8], frames: usize) -> Vec<testing::Draw> {
+        let mut rng = Randy::new();
+        rng.seed(777);
+        testing::play_game(&mut Replay::new(script), &mut rng, frames)
+    }
+
+    #[test]
+    fn replay_drives_a_game() {
+        let frames = 1500;
+        let script = testing::busy_script(frames);
+        let game = replay_game(&script, frames);
+        assert!(game.len() > 1000);
+
+        // the same game when the frames go straight into it
+        let mut rng = Randy::new();
+        rng.seed(777);
+        let mut drawing : testing::Drawing = Default::default();
+        let mut direct_game = Game::new(&mut rng);
+        for input in script.iter().map(|bits| decode_input(*bits)) {
+            let state = direct_game.run_loop(&input, &mut rng);
+            direct_game.draw(&mut drawing);
+            if matches!(state, GameState::GameOver) {
+                break;
+            }
+        }
+        assert_eq!(game, drawing.draws);
+
+        // pausing and restarting are up to main, the game never sees them
+        let with_actions : Vec<u8> =
+            script.iter()
+                  .enumerate()
+                  .map(|(i, bits)| bits | if i % 7 == 0 { PAUSE_BIT } else if i % 11 == 0 { RESTART_BIT } else { 0 })
+                  .collect();
+        assert_eq!(replay_game(&with_actions, frames), game);
+
+        // once the script runs out nothing is held down, same as if it had recorded that
+        let mut padded = script[..frames / 2].to_vec();
+        padded.resize(frames, 0);
+        assert_eq!(replay_game(&script[..frames / 2], frames), replay_game(&padded, frames));
+        assert_ne!(replay_game(&padded, frames), game);
+    }
+}
@@ -1,17 +1,31 @@
 use fourtris::game_renderer::GameRenderer;
 use fourtris::game_renderer::TetriminoType;
 
+use crate::clock;
+use crate::delay::Delay;
+use crate::font;
+use crate::highscore::{self, Entry};
+use crate::seed;
+use crate::settings::Palette;
+use crate::stats::{self, GameStats, LifetimeStats};
+
 #[repr(u8)]
 enum LcdCommand {
     SWRESET = 0x01,
+    SLPIN   = 0x10,
     SLPOUT  = 0x11,
+    PTLON   = 0x12,
     NORON   = 0x13,
     INVOFF  = 0x20,
+    DISPOFF = 0x28,
     DISPON  = 0x29,
     CASET   = 0x2A,
     RASET   = 0x2B,
     RAMWR   = 0x2C,
+    PTLAR   = 0x30,
     MADCTL  = 0x36,
+    IDMOFF  = 0x38,
+    IDMON   = 0x39,
     COLMOD  = 0x3A,
     FRMCTR1 = 0xB1,
     FRMCTR2 = 0xB2,
@@ -27,31 +41,27 @@ enum LcdCommand {
     GMCTRN1 = 0xE1,
 }
 
-fn mini_delay() {
-    for _ in 0..100000 {
-        unsafe {
-            asm!("nop");
-        }
-    }
-}
+/// The ST7735 can't take data any faster than this (66 ns write cycle)
+const MAX_SPI_CLOCK_HZ : u32 = 15_000_000;
 
 // Pins used for the LCD
 // PA4 - CS (Chip select)
 // PB4 - SPI CLK
 // PB7 - SSI2Tx (MOSI)
 // PF0 - !RESET
-// PF3 - Backlight UNUSED (only would need to be enabled if you had the jumper set appropriately)
+// PF3 - Backlight (see backlight.rs, only does anything if the jumper is set appropriately)
 // PF4 - D/CX (data/command)
 pub struct Lcd<'a> {
     p: &'a tm4c123x::Peripherals,
+    delay: Delay,
 }
 
 impl Lcd<'_> {
-    pub fn new(p: &tm4c123x::Peripherals) -> Lcd {
+    pub fn new(p: &tm4c123x::Peripherals, delay: Delay) -> Lcd {
         // --------------------------------------
         // HARDWARE INITIALIZATION
         // --------------------------------------
-        // port A and F setup - A4, F0, F3, F4 are needed as GPIO outputs
+        // port A and F setup - A4, F0, F4 are needed as GPIO outputs
         // --------------------------------------
         // 1. enable clock for port A and F GPIO pins
         p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits (r.bits() | 0x21) } );
@@ -65,17 +75,17 @@ impl Lcd<'_> {
         p.GPIO_PORTA_AHB.dir.modify(|r,w| unsafe { w.bits(r.bits() | 0x10) });
         // 3. enable pullup resistor on PA4. Default value should be high
         p.GPIO_PORTA_AHB.pur.modify(|r, w| unsafe { w.bits( r.bits() | 0x10) });
-        // 4. enable digital output on PA4
-        p.GPIO_PORTA_AHB.den.write(|w| unsafe { w.bits(0x10) });
+        // 4. enable digital output on PA4 (leave the UART pins alone)
+        p.GPIO_PORTA_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0x10) });
         // --------------------
         // --- PORT F setup ---
         // --------------------
-        // 5. set PF0, PF3, and PF4 as outputs
-        p.GPIO_PORTF_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() | 0x19 ) }); 
+        // 5. set PF0 and PF4 as outputs
+        p.GPIO_PORTF_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() | 0x11 ) }); 
         // 3. enable pullup resistor on PF0. Default value should be high
         p.GPIO_PORTF_AHB.pur.modify(|r, w| unsafe { w.bits( r.bits() | 1) });
-        // 6. enable digital output on PF0, PF3, and PF4 as outputs
-        p.GPIO_PORTF_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0x19 ) });
+        // 6. enable digital output on PF0 and PF4 as outputs
+        p.GPIO_PORTF_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0x11 ) });
 
         // --------------------------------------
         //    initialize and configure the SSI2
@@ -98,11 +108,14 @@ impl Lcd<'_> {
         // 7. set clock source - 0 = system clock, 5 = PIOSC (no idea what that is yet)
         p.SSI2.cc.modify(|r, w| unsafe { w.bits( (r.bits() & (!0xF)) | 0 /*5*/) });
         // 8. set prescale divisor (must be an even number between 2 and 254)
-        p.SSI2.cpsr.modify(|r,w| unsafe { w.bits( (r.bits() & (!0xFF)) |  4) });
+        //    SPI clock = system clock / (CPSDVSR * (1 + SCR)), as fast as the LCD can handle
+        let (cpsdvsr, scr) = clock::ssi_divisors(MAX_SPI_CLOCK_HZ);
+        p.SSI2.cpsr.modify(|r,w| unsafe { w.bits( (r.bits() & (!0xFF)) | cpsdvsr) });
         // 9. write to CR0 - serial clock rate (SCR), SPH, SPO, Protocol mode, Data size
+        //  bits 15:8 are SCR
         //  upper nibble is [SPH (1bit) | SPO (1bit) | FRF (2bits)]
         //  lower nibble is the data size HAHHAHAHAHAHAH i made it 9 bit data!!!!
-        p.SSI2.cr0.modify(|r, w| unsafe { w.bits( (r.bits() & !(0xFF))  | 7) });
+        p.SSI2.cr0.modify(|r, w| unsafe { w.bits( (r.bits() & !(0xFFFF)) | (scr << 8) | 7) });
         // 10. (optional) enable uDMA
 
         // 11. enable SSI2 module
@@ -113,21 +126,32 @@ impl Lcd<'_> {
 
         Lcd {
             p,
+            delay,
         }
     }
 
-    pub fn init(&self) {
+    /// Bring the LCD up from whatever state it's in. The BoosterPack doesn't connect the
+    /// LCD's data out line, so there's no way to ask it if it worked (RDDST and friends),
+    /// the best we can do is stick to the datasheet timings.
+    /// Leaves the display off, call display_on once something has been drawn.
+    pub fn init(&mut self) {
         // -------------------------------
         // CONFIGURE THE LCD FOR OPERATION
         // -------------------------------
-        self.reset_high();
+        // make sure nothing is left over in the SSI FIFO from before
+        while self.is_ssi_busy() {}
         self.cs_high();
+        self.hardware_reset();
 
-        mini_delay();
+        // ** SWRESET, in case the reset line didn't do the job (or isn't hooked up)
+        self.write_command(LcdCommand::SWRESET);
+        // 5 ms before the next command, but 120 ms before SLPOUT
+        self.delay.delay_ms(120);
 
         // ** SLPOUT command
         self.write_command(LcdCommand::SLPOUT);
-        mini_delay();
+        // the LCD needs 120 ms to wake up before it takes any more commands
+        self.delay.delay_ms(120);
 
         // ** set color mod: rgb 4-4-4
         let colmod_param = [3];
@@ -141,11 +165,58 @@ impl Lcd<'_> {
         self.write_data(&[0xC8], 0);
     }
 
-    pub fn display_on(&self) {
+    pub fn display_on(&mut self) {
         // ** turn the display on
         self.write_command(LcdCommand::DISPON);
         // wait at least 120 ms
-        mini_delay();
+        self.delay.delay_ms(120);
+    }
+
+    /// Blank the panel, whatever is in the frame memory stays there
+    pub fn display_off(&mut self) {
+        self.write_command(LcdCommand::DISPOFF);
+    }
+
+    /// Stop the panel, booster and oscillator. The frame memory is kept, so `sleep_out` brings
+    /// back whatever was on the screen.
+    pub fn sleep_in(&mut self) {
+        self.write_command(LcdCommand::SLPIN);
+        // nothing else for 5 ms, and no SLPOUT for 120 ms
+        self.delay.delay_ms(120);
+    }
+
+    pub fn sleep_out(&mut self) {
+        self.write_command(LcdCommand::SLPOUT);
+        // the LCD needs 120 ms to wake up before it takes any more commands
+        self.delay.delay_ms(120);
+    }
+
+    /// Idle mode only shows 8 colors (the top bit of red, green and blue), which takes less power
+    pub fn idle_mode(&mut self, on: bool) {
+        self.write_command(if on { LcdCommand::IDMON } else { LcdCommand::IDMOFF });
+    }
+
+    /// Only refresh the rows from `start` to `end` (inclusive, same coordinates as
+    /// set_drawing_area), everything else goes blank. `normal_mode` undoes it.
+    pub fn partial_mode(&mut self, start: u8, end: u8) {
+        self.write_command(LcdCommand::PTLAR);
+        self.write_data(&[0, 3 + start, 0, 3 + end], 0);
+        self.write_command(LcdCommand::PTLON);
+    }
+
+    /// Refresh the whole panel again, after `partial_mode`
+    pub fn normal_mode(&mut self) {
+        self.write_command(LcdCommand::NORON);
+    }
+
+    /// Pulse !RESET, which puts every LCD register back to its default and the LCD to sleep
+    pub fn hardware_reset(&mut self) {
+        // !RESET has to be low for at least 10 us to count as a reset
+        self.reset_low();
+        self.delay.delay_us(10);
+        self.reset_high();
+        // and the LCD needs up to 120 ms to finish resetting
+        self.delay.delay_ms(120);
     }
 
     // -------------------------------------
@@ -264,20 +335,48 @@ impl Lcd<'_> {
 
 pub struct LcdBackend<'a> {
     lcd: Lcd<'a>,
+    /// number of pixels of the restart bar that are currently filled in
+    restart_progress: u8,
+    /// the level that was drawn last
+    level: usize,
+    // copies of everything on the screen, so it can be redrawn after the LCD gets reset
+    /// pixel data for each kind of block
+    colors: &'static [[u8; 3]; 8],
+    /// color of every block in the playing field (an index into colors)
+    board: [u8; PLAYFIELD_BLOCKS],
+    score: u32,
+    /// seed and the digit being edited
+    seed: Option<(u32, Option<usize>)>,
+    frame_rate: Option<u32>,
+    load: Option<u32>,
+    /// idle and partial mode are on
+    power_saving: bool,
+    /// the game is on the screen (and not the title screen or initials entry)
+    game_screen: bool,
 }
 
 impl LcdBackend<'_> {
-    pub fn new(lcd: Lcd) -> LcdBackend {
+    pub fn new(mut lcd: Lcd) -> LcdBackend {
         lcd.init();
         LcdBackend {
             lcd,
+            restart_progress: 0,
+            level: 1,
+            colors: &CLASSIC_COLORS,
+            board: [EMPTY_BLOCK; PLAYFIELD_BLOCKS],
+            score: 0,
+            seed: None,
+            frame_rate: None,
+            load: None,
+            power_saving: false,
+            game_screen: false,
         }
     }
 
-    pub fn draw_initial_screen(&mut self) {
+    fn draw_initial_screen(&mut self) {
+        self.game_screen = true;
         // 1. clear the screen to white
-        self.lcd.set_drawing_area(0, 0, 128, 128);
-        self.lcd.draw_pixels_repeatedly(&[0xFF, 0xFF, 0xFF], 8192);
+        self.clear_screen();
 
         // 2. draw the playfield
         self.lcd.set_drawing_area(PLAYFIELD_HORIZONTAL_PADDING,
@@ -308,11 +407,133 @@ impl LcdBackend<'_> {
         self.draw_score(0);
     }
 
-    pub fn turn_on_display(&self) {
+    pub fn turn_on_display(&mut self) {
         self.lcd.display_on();
     }
 
-    pub fn clear_playing_field(&self) {
+    /// Reset the LCD and draw everything on it again, for when it's showing garbage
+    /// (static electricity can do that)
+    pub fn recover(&mut self) {
+        self.lcd.init();
+        self.redraw();
+
+        // the reset turned idle and partial mode off
+        if self.power_saving {
+            self.start_power_saving();
+        }
+
+        self.lcd.display_on();
+    }
+
+    /// Get the screen ready for a new game, going back to the game screen if it isn't up
+    pub fn new_game(&mut self) {
+        if self.game_screen {
+            self.clear_playing_field();
+            self.clear_restart_progress();
+            self.draw_score(0);
+            self.draw_level(1);
+        } else {
+            self.board = [EMPTY_BLOCK; PLAYFIELD_BLOCKS];
+            self.score = 0;
+            self.level = 1;
+            self.redraw();
+        }
+    }
+
+    /// Start a game over without showing it, for replaying a saved game. The game draws to the
+    /// copies of the screen only, until end_restore puts it all up at once.
+    pub fn begin_restore(&mut self) {
+        self.game_screen = false;
+        self.board = [EMPTY_BLOCK; PLAYFIELD_BLOCKS];
+        self.score = 0;
+        self.level = 1;
+    }
+
+    pub fn end_restore(&mut self) {
+        self.redraw();
+    }
+
+    /// Draw the game screen from scratch, with everything that's supposed to be on it
+    fn redraw(&mut self) {
+        let score = self.score;
+        let level = self.level;
+        self.draw_initial_screen();
+        self.draw_score(score);
+        self.draw_level(level);
+
+        for (i, color) in self.board.iter().enumerate() {
+            if *color != EMPTY_BLOCK {
+                self.fill_block((i % PLAYFIELD_COLUMNS) as u8, (i / PLAYFIELD_COLUMNS) as u8, *color);
+            }
+        }
+        if let Some((seed, cursor)) = self.seed {
+            self.draw_seed(seed, cursor);
+        }
+        if let Some(hz) = self.frame_rate {
+            self.draw_frame_rate(hz);
+        }
+        if let Some(percent) = self.load {
+            self.draw_load(percent);
+        }
+        // the white background wiped out the restart bar
+        self.restart_progress = 0;
+    }
+
+    /// While nothing is moving (like when the game is paused), drop to 8 colors and only
+    /// refresh the rows that have something on them
+    pub fn set_power_saving(&mut self, on: bool) {
+        if on == self.power_saving {
+            return;
+        }
+        self.power_saving = on;
+        if on {
+            self.start_power_saving();
+        } else {
+            self.lcd.normal_mode();
+            self.lcd.idle_mode(false);
+        }
+    }
+
+    fn start_power_saving(&mut self) {
+        self.lcd.idle_mode(true);
+        // the playfield and restart bar cover everything else on the screen too
+        self.lcd.partial_mode(PLAYFIELD_VERTICAL_PADDING, RESTART_BAR_Y + RESTART_BAR_HEIGHT - 1);
+    }
+
+    /// Blank the screen and put the LCD to sleep, what's on the screen is kept for `wake`
+    pub fn sleep(&mut self) {
+        self.lcd.display_off();
+        self.lcd.sleep_in();
+    }
+
+    pub fn wake(&mut self) {
+        self.lcd.sleep_out();
+        self.lcd.display_on();
+    }
+
+    /// Colors for the pieces, this only shows up on blocks drawn after the change
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.colors =
+            match palette {
+                Palette::Classic => &CLASSIC_COLORS,
+                Palette::Guideline => &GUIDELINE_COLORS,
+            };
+    }
+
+    /// Draw one block of the playing field, `color` is an index into the palette
+    fn fill_block(&self, x: u8, y: u8, color: u8) {
+        // set the drawing area for the tetrimino
+        let xs = (x as u8)*BLOCK_WIDTH + PLAYFIELD_HORIZONTAL_PADDING;
+        let ys = (y as u8)*BLOCK_WIDTH + PLAYFIELD_VERTICAL_PADDING;
+        self.lcd.set_drawing_area(xs, ys, BLOCK_WIDTH, BLOCK_WIDTH);
+
+        // write pixel data
+        let repeat_count = (BLOCK_WIDTH * BLOCK_WIDTH) >> 1;
+        self.lcd.draw_pixels_repeatedly(&self.colors[color as usize][..], repeat_count as usize);
+    }
+
+    pub fn clear_playing_field(&mut self) {
+        self.board = [EMPTY_BLOCK; PLAYFIELD_BLOCKS];
         // make the playing field black
         self.lcd.set_drawing_area(PLAYFIELD_HORIZONTAL_PADDING as u8,
                            PLAYFIELD_VERTICAL_PADDING as u8,
@@ -320,58 +541,353 @@ impl LcdBackend<'_> {
                            PLAYFIELD_HEIGHT as u8);
         self.lcd.draw_pixels_repeatedly(&[0x00, 0x00, 0x00], 2750);
     }
+
+    /// Fill in the restart bar underneath the playing field, `filled` is in pixels
+    /// and is capped at RESTART_BAR_WIDTH
+    pub fn draw_restart_progress(&mut self, filled: u8) {
+        if !self.game_screen {
+            return;
+        }
+        let filled = if filled > RESTART_BAR_WIDTH { RESTART_BAR_WIDTH } else { filled };
+        if filled < self.restart_progress {
+            self.clear_restart_progress();
+        }
+        if filled == self.restart_progress {
+            return;
+        }
+
+        // only draw the part of the bar that is new
+        let new_pixels = filled - self.restart_progress;
+        self.lcd.set_drawing_area(PLAYFIELD_HORIZONTAL_PADDING + self.restart_progress,
+                                  RESTART_BAR_Y,
+                                  new_pixels,
+                                  RESTART_BAR_HEIGHT);
+        // 2 pixels per 3 bytes, round up so we don't leave a pixel behind
+        let repeat_count = (new_pixels as usize * RESTART_BAR_HEIGHT as usize + 1) / 2 - 1;
+        // red
+        self.lcd.draw_pixels_repeatedly(&[0xF0, 0x0F, 0x00], repeat_count);
+        self.restart_progress = filled;
+    }
+
+    /// Erase the restart bar
+    pub fn clear_restart_progress(&mut self) {
+        if !self.game_screen || self.restart_progress == 0 {
+            return;
+        }
+        self.lcd.set_drawing_area(PLAYFIELD_HORIZONTAL_PADDING,
+                                  RESTART_BAR_Y,
+                                  RESTART_BAR_WIDTH,
+                                  RESTART_BAR_HEIGHT);
+        let repeat_count = (RESTART_BAR_WIDTH as usize * RESTART_BAR_HEIGHT as usize) / 2 - 1;
+        self.lcd.draw_pixels_repeatedly(&[0xFF, 0xFF, 0xFF], repeat_count);
+        self.restart_progress = 0;
+    }
+
+    /// Write ASCII text with the font in font.rs, every character takes up 5 pixels
+    /// (4 for the glyph, 1 for the space after it). `inverted` swaps the text and
+    /// background colors.
+    pub fn draw_text(&self, x: u8, y: u8, text: &[u8], inverted: bool) {
+        let (foreground, background) =
+            if inverted {
+                (BACKGROUND_COLOR, TEXT_COLOR)
+            } else {
+                (TEXT_COLOR, BACKGROUND_COLOR)
+            };
+        let mut x = x;
+        for c in text {
+            let glyph = font::glyph(*c);
+            let mut pixels = [0u8; CHAR_BYTES];
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..CHAR_WIDTH as usize {
+                    // the last column is the space between characters
+                    let lit = column < font::GLYPH_WIDTH as usize && bits & (0b1000 >> column) != 0;
+                    set_pixel(&mut pixels,
+                              row * CHAR_WIDTH as usize + column,
+                              if lit { foreground } else { background });
+                }
+            }
+            self.lcd.set_drawing_area(x, y, CHAR_WIDTH, font::GLYPH_HEIGHT);
+            self.lcd.draw_pixels(&pixels);
+            x += CHAR_WIDTH;
+        }
+    }
+
+    /// Show the seed on the left side underneath the level, with the digit at `cursor`
+    /// highlighted while the seed is being picked
+    pub fn draw_seed(&mut self, seed: u32, cursor: Option<usize>) {
+        self.seed = Some((seed, cursor));
+        if !self.game_screen {
+            return;
+        }
+        let label_x = (PLAYFIELD_HORIZONTAL_PADDING - text_width(4)) / 2;
+        self.draw_text(label_x, SEED_TEXT_Y, b"SEED", false);
+
+        let digits = seed::seed_text(seed);
+        let mut x = (PLAYFIELD_HORIZONTAL_PADDING - text_width(digits.len() as u8)) / 2;
+        for (i, digit) in digits.iter().enumerate() {
+            self.draw_text(x, SEED_TEXT_Y + 12, &[*digit], cursor == Some(i));
+            x += CHAR_WIDTH;
+        }
+    }
+
+    /// The level the game is on, as far as the screen knows
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// The score the game is at, as far as the screen knows
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Number of blocks in the playing field that aren't empty, the falling piece included
+    pub fn filled_blocks(&self) -> usize {
+        self.board.iter().filter(|block| **block != EMPTY_BLOCK).count()
+    }
+
+    /// The title screen, with the high score table. The row at `highlight` is inverted,
+    /// for a score that just made it in.
+    pub fn draw_title(&mut self, entries: &[Entry], highlight: Option<usize>) {
+        self.game_screen = false;
+        self.clear_screen();
+        self.draw_centered(TITLE_Y, b"FOURTRIS", false);
+
+        self.draw_text(TABLE_X, TABLE_HEADER_Y, b"   WHO   SCORE LV LINE", false);
+        for rank in 0..highscore::ENTRIES {
+            // " 1 ABC 1234567 12 1234"
+            let mut row = [b' '; TABLE_COLUMNS];
+            number_text(rank as u32 + 1, &mut row[0..2]);
+            match entries.get(rank) {
+                Some(entry) => {
+                    row[3..6].copy_from_slice(&entry.initials);
+                    number_text(entry.score, &mut row[7..14]);
+                    number_text(entry.level as u32, &mut row[15..17]);
+                    number_text(entry.lines, &mut row[18..22]);
+                },
+                None => row[3..6].copy_from_slice(b"---"),
+            }
+            self.draw_text(TABLE_X,
+                           TABLE_Y + rank as u8 * TABLE_ROW_HEIGHT,
+                           &row,
+                           highlight == Some(rank));
+        }
+
+        self.draw_centered(FOOTER_Y, b"TAP:PLAY S1:MENU DN:STATS", false);
+    }
+
+    /// Let the player know they got a high score, draw_initials fills in the rest
+    pub fn draw_initials_screen(&mut self, score: u32) {
+        self.game_screen = false;
+        self.clear_screen();
+        self.draw_centered(INITIALS_SCREEN_Y, b"NEW HIGH SCORE", false);
+
+        let mut digits = [b' '; 10];
+        number_text(score, &mut digits);
+        let first_digit = digits.iter().position(|c| *c != b' ').unwrap_or(0);
+        self.draw_centered(INITIALS_SCREEN_Y + 16, &digits[first_digit..], false);
+
+        self.draw_centered(INITIALS_SCREEN_Y + 36, b"ENTER INITIALS", false);
+        self.draw_centered(INITIALS_SCREEN_Y + 72, b"LEFT RIGHT: LETTER", false);
+        self.draw_centered(INITIALS_SCREEN_Y + 82, b"DOWN: NEXT", false);
+    }
+
+    /// The initials being typed in, with the letter at `cursor` highlighted
+    pub fn draw_initials(&mut self, initials: &[u8; highscore::INITIALS], cursor: usize) {
+        let mut x = (SCREEN_SIZE - text_width(initials.len() as u8)) / 2;
+        for (i, letter) in initials.iter().enumerate() {
+            self.draw_text(x, INITIALS_SCREEN_Y + 52, &[*letter], cursor == i);
+            x += CHAR_WIDTH;
+        }
+    }
+
+    /// Lifetime stats, and how many of each piece the last game got next to the lifetime totals
+    pub fn draw_stats_screen(&mut self, lifetime: &LifetimeStats, last_game: &GameStats) {
+        self.game_screen = false;
+        self.clear_screen();
+        self.draw_centered(TITLE_Y, b"STATISTICS", false);
+
+        // "GAMES PLAYED        1234", the value on the right
+        let mut y = STATS_Y;
+        let totals : [(&[u8], u32); 3] = [
+            (b"GAMES PLAYED", lifetime.games),
+            (b"LINES", lifetime.lines),
+            (b"BEST LEVEL", lifetime.best_level),
+        ];
+        for (label, value) in totals.iter() {
+            let mut row = [b' '; STATS_COLUMNS];
+            row[..label.len()].copy_from_slice(label);
+            number_text(*value, &mut row[STATS_COLUMNS - 10..]);
+            self.draw_text(STATS_X, y, &row, false);
+            y += TABLE_ROW_HEIGHT;
+        }
+
+        // "PLAY TIME     1234:05:09"
+        let mut row = [b' '; STATS_COLUMNS];
+        row[..9].copy_from_slice(b"PLAY TIME");
+        let minutes = lifetime.seconds / 60;
+        let time = &mut row[STATS_COLUMNS - 10..];
+        number_text(minutes / 60, &mut time[..4]);
+        time[4] = b':';
+        time[5] = b'0' + (minutes % 60 / 10) as u8;
+        time[6] = b'0' + (minutes % 10) as u8;
+        time[7] = b':';
+        time[8] = b'0' + (lifetime.seconds % 60 / 10) as u8;
+        time[9] = b'0' + (lifetime.seconds % 10) as u8;
+        self.draw_text(STATS_X, y, &row, false);
+        y += TABLE_ROW_HEIGHT + 3;
+
+        // "  T       1234567    123"
+        self.draw_text(STATS_X, y, b"PIECE     LIFETIME  LAST", false);
+        for (i, name) in stats::PIECE_NAMES.iter().enumerate() {
+            y += TABLE_ROW_HEIGHT;
+            let mut row = [b' '; STATS_COLUMNS];
+            row[2] = *name;
+            number_text(lifetime.pieces[i], &mut row[8..18]);
+            number_text(last_game.pieces[i], &mut row[19..24]);
+            self.draw_text(STATS_X, y, &row, false);
+        }
+    }
+
+    /// Offer to pick up the game that was saved before the power went out
+    pub fn draw_continue_screen(&mut self, score: u32, level: usize) {
+        self.game_screen = false;
+        self.clear_screen();
+        self.draw_centered(TITLE_Y, b"FOURTRIS", false);
+        self.draw_centered(CONTINUE_SCREEN_Y, b"CONTINUE?", false);
+
+        let mut row = [b' '; 13];
+        row[..5].copy_from_slice(b"SCORE");
+        number_text(score, &mut row[6..]);
+        self.draw_centered(CONTINUE_SCREEN_Y + 20, &row, false);
+        row = [b' '; 13];
+        row[..5].copy_from_slice(b"LEVEL");
+        number_text(level as u32, &mut row[6..]);
+        self.draw_centered(CONTINUE_SCREEN_Y + 30, &row, false);
+
+        self.draw_centered(CONTINUE_SCREEN_Y + 56, b"TAP: CONTINUE", false);
+        self.draw_centered(CONTINUE_SCREEN_Y + 66, b"S1: NEW GAME", false);
+    }
+
+    /// The settings menu, draw_menu_row fills in the settings
+    pub fn draw_menu_screen(&mut self) {
+        self.game_screen = false;
+        self.clear_screen();
+        self.draw_centered(TITLE_Y, b"SETTINGS", false);
+        self.draw_centered(FOOTER_Y, b"TAP BUTTON TO SAVE", false);
+    }
+
+    /// One setting in the menu: `label` on the left (highlighted if it's `selected`),
+    /// and `value` on the right
+    pub fn draw_menu_row(&mut self, row: usize, label: &[u8], value: &[u8], selected: bool) {
+        let y = MENU_Y + row as u8 * MENU_ROW_HEIGHT;
+        // values aren't all the same length, get rid of the old one
+        self.lcd.set_drawing_area(0, y, SCREEN_SIZE, TEXT_HEIGHT);
+        self.lcd.draw_pixels_repeatedly(&[0xFF, 0xFF, 0xFF],
+                                        SCREEN_SIZE as usize * TEXT_HEIGHT as usize / 2 - 1);
+
+        self.draw_text(MENU_MARGIN, y, label, selected);
+        let value_x = SCREEN_SIZE - MENU_MARGIN - text_width(value.len() as u8);
+        self.draw_text(value_x, y, value, false);
+    }
+
+    fn draw_centered(&self, y: u8, text: &[u8], inverted: bool) {
+        self.draw_text((SCREEN_SIZE - text_width(text.len() as u8)) / 2, y, text, inverted);
+    }
+
+    fn clear_screen(&self) {
+        self.lcd.set_drawing_area(0, 0, SCREEN_SIZE, SCREEN_SIZE);
+        self.lcd.draw_pixels_repeatedly(&[0xFF, 0xFF, 0xFF], 8192);
+    }
+
+    /// Show the frame rate on the right side underneath the score
+    pub fn draw_frame_rate(&mut self, hz: u32) {
+        self.frame_rate = Some(hz);
+        if !self.game_screen {
+            return;
+        }
+        let right_side = PLAYFIELD_HORIZONTAL_PADDING + PLAYFIELD_WIDTH;
+        let label_x = right_side + (PLAYFIELD_HORIZONTAL_PADDING - text_width(3)) / 2;
+        self.draw_text(label_x, FRAME_RATE_TEXT_Y, b"FPS", false);
+
+        let digits = [b'0' + (hz / 10 % 10) as u8, b'0' + (hz % 10) as u8];
+        let digits_x = right_side + (PLAYFIELD_HORIZONTAL_PADDING - text_width(2)) / 2;
+        self.draw_text(digits_x, FRAME_RATE_TEXT_Y + 12, &digits, false);
+    }
+
+    /// Show how much of the frame budget the worst frame used (in percent) underneath the seed
+    pub fn draw_load(&mut self, percent: u32) {
+        self.load = Some(percent);
+        if !self.game_screen {
+            return;
+        }
+        let label_x = (PLAYFIELD_HORIZONTAL_PADDING - text_width(3)) / 2;
+        self.draw_text(label_x, LOAD_TEXT_Y, b"CPU", false);
+
+        let percent = if percent > 999 { 999 } else { percent };
+        let digits = [b'0' + (percent / 100) as u8,
+                      b'0' + (percent / 10 % 10) as u8,
+                      b'0' + (percent % 10) as u8];
+        // over budget shows up inverted
+        self.draw_text(label_x, LOAD_TEXT_Y + 12, &digits, percent > 100);
+    }
+}
+
+/// Set one pixel in a buffer of 12 bit pixels (2 pixels per 3 bytes)
+fn set_pixel(buf: &mut [u8], index: usize, color: u16) {
+    let offset = index / 2 * 3;
+    if index % 2 == 0 {
+        buf[offset] = (color >> 4) as u8;
+        buf[offset + 1] = (buf[offset + 1] & 0x0F) | ((color & 0xF) << 4) as u8;
+    } else {
+        buf[offset + 1] = (buf[offset + 1] & 0xF0) | (color >> 8) as u8;
+        buf[offset + 2] = color as u8;
+    }
+}
+
+/// `value` in decimal, right aligned with spaces in front. Anything too big for `text` shows
+/// up as all 9s.
+fn number_text(value: u32, text: &mut [u8]) {
+    let max = 10u32.saturating_pow(text.len() as u32) - 1;
+    let mut value = if value > max { max } else { value };
+    for (i, c) in text.iter_mut().rev().enumerate() {
+        *c = if i == 0 || value > 0 { b'0' + (value % 10) as u8 } else { b' ' };
+        value /= 10;
+    }
+}
+
+/// Width of `chars` characters of text, without the space after the last one
+const fn text_width(chars: u8) -> u8 {
+    chars * CHAR_WIDTH - 1
 }
 
 impl GameRenderer for LcdBackend<'_> {
     fn draw_block(&mut self, x: u8, y: u8, tetrimino_type: TetriminoType) {
-        let pixel_data = 
+        let color =
             match tetrimino_type {
-                TetriminoType::I => {
-                    // blue
-                    [0x00, 0xF0, 0x0F]
-                },
-                TetriminoType::O => {
-                    // green
-                    [0x0A, 0x00, 0xA0]
-                },
-                TetriminoType::J => {
-                    // cyan
-                    [0x0A, 0xA0, 0xAA]
-                },
-                TetriminoType::L => {
-                    // red
-                    [0xF0, 0x0F, 0x00]
-                },
-                TetriminoType::S => {
-                    // purple
-                    [0xA0, 0xAA, 0x0A]
-                },
-                TetriminoType::Z => {
-                    // yellow
-                    [0xAA, 0x0A, 0xA0]
-                    //[0x77, 0x07, 0x70]
-                },
-                TetriminoType::T => {
-                    // lime green
-                    [0x7F, 0x77, 0xF7]
-                },
-                TetriminoType::EmptySpace => {
-                    // black
-                    [0x00, 0x00, 0x00]
-                },
+                TetriminoType::I => 0,
+                TetriminoType::O => 1,
+                TetriminoType::J => 2,
+                TetriminoType::L => 3,
+                TetriminoType::S => 4,
+                TetriminoType::Z => 5,
+                TetriminoType::T => 6,
+                TetriminoType::EmptySpace => EMPTY_BLOCK,
             };
 
-        // set the drawing area for the tetrimino
-        let xs = (x as u8)*BLOCK_WIDTH + PLAYFIELD_HORIZONTAL_PADDING;
-        let ys = (y as u8)*BLOCK_WIDTH + PLAYFIELD_VERTICAL_PADDING;
-        self.lcd.set_drawing_area(xs, ys, BLOCK_WIDTH, BLOCK_WIDTH);
-
-        // write pixel data
-        let repeat_count = (BLOCK_WIDTH * BLOCK_WIDTH) >> 1;
-        self.lcd.draw_pixels_repeatedly(&pixel_data[..], repeat_count as usize);
+        if let Some(block) = self.board.get_mut(y as usize * PLAYFIELD_COLUMNS + x as usize) {
+            *block = color;
+        }
+        // off the game screen (see begin_restore) only the copy gets updated
+        if self.game_screen {
+            self.fill_block(x, y, color);
+        }
     }
 
     fn draw_score(&mut self, score: u32) {
+        self.score = score;
+        if !self.game_screen {
+            return;
+        }
         // erase the old score displayed
         let side_padding = (PLAYFIELD_HORIZONTAL_PADDING - SCORE_TEXT_WIDTH) / 2;
         self.lcd.set_drawing_area(PLAYFIELD_HORIZONTAL_PADDING + PLAYFIELD_WIDTH + side_padding,
@@ -413,6 +929,10 @@ impl GameRenderer for LcdBackend<'_> {
 
 
     fn draw_level(&mut self, level: usize) {
+        self.level = level;
+        if !self.game_screen {
+            return;
+        }
         // erase the old level number displayed
         self.lcd.set_drawing_area((PLAYFIELD_HORIZONTAL_PADDING - LEVEL_TEXT_WIDTH) / 2,
                                   12 + PLAYFIELD_VERTICAL_PADDING as u8,
@@ -452,11 +972,88 @@ const TEXT_HEIGHT : u8 = 8;
 const NUMBER_CHAR_WIDTH : u8 = 4;
 const SCORE_TEXT_WIDTH : u8 = 24;
 const LEVEL_TEXT_WIDTH : u8 = 25;
+/// a character from font.rs plus the space after it
+const CHAR_WIDTH : u8 = font::GLYPH_WIDTH + 1;
+/// 2 pixels per 3 bytes
+const CHAR_BYTES : usize = (CHAR_WIDTH as usize * font::GLYPH_HEIGHT as usize) / 2 * 3;
+const TEXT_COLOR : u16 = 0xF00;
+const BACKGROUND_COLOR : u16 = 0xFFF;
+/// the seed goes underneath the level number
+const SEED_TEXT_Y : u8 = PLAYFIELD_VERTICAL_PADDING + 36;
+/// the frame rate goes underneath the score, lined up with the seed
+const FRAME_RATE_TEXT_Y : u8 = SEED_TEXT_Y;
+/// the profiler's CPU load goes underneath the seed
+const LOAD_TEXT_Y : u8 = SEED_TEXT_Y + 30;
+const SCREEN_SIZE : u8 = 128;
+// the title screen
+const TITLE_Y : u8 = 2;
+const TABLE_HEADER_Y : u8 = 14;
+const TABLE_Y : u8 = 26;
+const TABLE_ROW_HEIGHT : u8 = 9;
+/// rank, initials, score, level and lines, with a space between each
+const TABLE_COLUMNS : usize = 22;
+const TABLE_X : u8 = (SCREEN_SIZE - TABLE_COLUMNS as u8 * CHAR_WIDTH) / 2;
+const FOOTER_Y : u8 = SCREEN_SIZE - TEXT_HEIGHT - 1;
+// the settings menu
+const MENU_Y : u8 = 16;
+const MENU_ROW_HEIGHT : u8 = 11;
+const MENU_MARGIN : u8 = 4;
+/// top of the initials entry screen
+const INITIALS_SCREEN_Y : u8 = 16;
+const CONTINUE_SCREEN_Y : u8 = 24;
+const STATS_Y : u8 = 14;
+const STATS_COLUMNS : usize = 24;
+const STATS_X : u8 = (SCREEN_SIZE - STATS_COLUMNS as u8 * CHAR_WIDTH) / 2;
 const BLOCK_WIDTH : u8 = 5;
 const PLAYFIELD_HORIZONTAL_PADDING : u8 = 39;
 const PLAYFIELD_VERTICAL_PADDING : u8 = 9;
 const PLAYFIELD_WIDTH : u8 = 10 * BLOCK_WIDTH;
 const PLAYFIELD_HEIGHT : u8 = 22 * BLOCK_WIDTH;
+const PLAYFIELD_COLUMNS : usize = 10;
+const PLAYFIELD_BLOCKS : usize = PLAYFIELD_COLUMNS * 22;
+/// pixel data for each kind of block, in TetriminoType order
+const CLASSIC_COLORS : [[u8; 3]; 8] = [
+    // I - blue
+    [0x00, 0xF0, 0x0F],
+    // O - green
+    [0x0A, 0x00, 0xA0],
+    // J - cyan
+    [0x0A, 0xA0, 0xAA],
+    // L - red
+    [0xF0, 0x0F, 0x00],
+    // S - purple
+    [0xA0, 0xAA, 0x0A],
+    // Z - yellow
+    [0xAA, 0x0A, 0xA0],
+    // T - lime green
+    [0x7F, 0x77, 0xF7],
+    // empty space - black
+    [0x00, 0x00, 0x00],
+];
+/// the Tetris guideline colors, in TetriminoType order
+const GUIDELINE_COLORS : [[u8; 3]; 8] = [
+    // I - cyan
+    [0x0F, 0xF0, 0xFF],
+    // O - yellow
+    [0xFF, 0x0F, 0xF0],
+    // J - blue
+    [0x00, 0xF0, 0x0F],
+    // L - orange
+    [0xF8, 0x0F, 0x80],
+    // S - green
+    [0x0F, 0x00, 0xF0],
+    // Z - red
+    [0xF0, 0x0F, 0x00],
+    // T - purple
+    [0xA0, 0xFA, 0x0F],
+    // empty space - black
+    [0x00, 0x00, 0x00],
+];
+const EMPTY_BLOCK : u8 = 7;
+pub const RESTART_BAR_WIDTH : u8 = PLAYFIELD_WIDTH;
+const RESTART_BAR_HEIGHT : u8 = 3;
+// leave a couple of pixels between the playing field and the bar
+const RESTART_BAR_Y : u8 = PLAYFIELD_VERTICAL_PADDING + PLAYFIELD_HEIGHT + 2;
 // 26 x 8 (actually 25 x 8 - but it's easier to specify data with pixels evenly
 const LEVEL_TEXT : [u8; 312] = [
     0xF0,0x0F,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,0x00,0xF0,0x0F,0x00,0xF0,0x0F,0xFF,0xF0,0x0F,0xFF,0xFF,0xFF,0xFF,0xF0,0x0F,0xFF,0xF0,0x0F,0x00,0xF0,0x0F,0x00,0xFF,0xFF,0x00,0xFF,0xFF,0xFF,0xFF,0xFF,0xFF,
@@ -0,0 +1,26 @@
+// Everything that doesn't need the hardware, so it can be built and tested on a PC:
+//   cargo test --lib --target x86_64-unknown-linux-gnu
+// (or whatever `rustc -vV` says the host is, .cargo/config.toml builds for the board by default).
+// The EEPROM and flash drivers live in the binary, everything here only sees them through
+// eeprom::Storage and flash::Storage, so the tests can save to RAM instead.
+#![cfg_attr(not(test), no_std)]
+
+pub mod clock;
+pub mod crc;
+pub mod eeprom;
+pub mod entropy;
+pub mod flash;
+pub mod highscore;
+pub mod input;
+pub mod lines;
+pub mod music;
+pub mod randomizer;
+pub mod randy;
+pub mod seed;
+pub mod settings;
+pub mod speed;
+pub mod stats;
+pub mod suspend;
+
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,111 @@
+// fourtris doesn't say how many lines have been cleared, so they're worked out from the blocks
+// on the screen instead (see LcdBackend::filled_blocks).
+//
+// The falling piece is always 4 blocks. Between two frames the number of filled blocks only
+// changes when pieces spawn (4 more blocks each) and lines are cleared (10 fewer each):
+//   change = 4 * spawns - 10 * lines
+// 4 * spawns ends in a different digit for 0 to 4 spawns, so the last digit of the change says
+// how many pieces spawned, and the rest is lines.
+
+/// More pieces than this can't spawn in one frame, it takes way more game ticks than that
+const MAX_SPAWNS : i32 = 4;
+
+pub struct LineCounter {
+    /// filled blocks last frame
+    filled: usize,
+    lines: u32,
+}
+
+impl LineCounter {
+    pub fn new() -> LineCounter {
+        LineCounter {
+            filled: 0,
+            lines: 0,
+        }
+    }
+
+    /// Start counting again, for a new game on an empty playing field
+    pub fn reset(&mut self) {
+        self.filled = 0;
+        self.lines = 0;
+    }
+
+    /// Call after every frame is drawn, returns the lines cleared since the last one
+    pub fn update(&mut self, filled: usize) -> u32 {
+        let change = filled as i32 - self.filled as i32;
+        self.filled = filled;
+        let cleared = (0..=MAX_SPAWNS)
+                          .map(|spawns| 4 * spawns - change)
+                          .find(|removed| *removed >= 0 && *removed % 10 == 0)
+                          .map_or(0, |removed| (removed / 10) as u32);
+        self.lines += cleared;
+        cleared
+    }
+
+    /// Lines cleared so far this game
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+}
+
+impl Default for LineCounter {
+    fn default() -> LineCounter {
+        LineCounter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawns_alone_are_not_lines() {
+        let mut counter = LineCounter::new();
+        assert_eq!(counter.update(0), 0);
+        assert_eq!(counter.update(4), 0);
+        // the piece landing and the next one spawning
+        assert_eq!(counter.update(8), 0);
+        // a frame with lots of game ticks can spawn a few
+        assert_eq!(counter.update(8 + 4 * MAX_SPAWNS as usize), 0);
+        assert_eq!(counter.lines(), 0);
+    }
+
+    #[test]
+    fn clears_are_counted() {
+        let mut counter = LineCounter::new();
+        counter.update(60);
+        // a single, with nothing spawning yet
+        assert_eq!(counter.update(50), 1);
+        // a double and the next piece in the same frame
+        assert_eq!(counter.update(34), 2);
+        assert_eq!(counter.lines(), 3);
+    }
+
+    #[test]
+    fn four_lines_and_four_spawns_in_one_frame() {
+        let mut counter = LineCounter::new();
+        counter.update(44);
+        // -40 + 16
+        assert_eq!(counter.update(20), 4);
+        assert_eq!(counter.lines(), 4);
+
+        // and a tetris that empties the field, with a new piece on top
+        let mut counter = LineCounter::new();
+        counter.update(44);
+        assert_eq!(counter.update(4), 4);
+    }
+
+    #[test]
+    fn reset_starts_a_new_game() {
+        let mut counter = LineCounter::new();
+        counter.update(40);
+        counter.update(30);
+        assert_eq!(counter.lines(), 1);
+
+        counter.reset();
+        assert_eq!(counter.lines(), 0);
+        // the first piece of the new game
+        assert_eq!(counter.update(4), 0);
+        assert_eq!(counter.lines(), 0);
+    }
+}
@@ -1,6 +1,5 @@
 #![no_main]
 #![no_std]
-#![feature(asm)]
 
 use panic_halt as _;
 
@@ -9,105 +8,199 @@ use fourtris::game::{Game, GameState, Input};
 use fourtris::game_renderer::GameRenderer;
 //use cortex_m_semihosting::{debug, hprintln};
 
+// the modules from mcu_fourtris (src/lib.rs) don't touch the hardware, so they can be tested on a PC
+mod adc;
+use adc::Adc;
+
+mod audio;
+use audio::{Audio, Effect};
+
+mod backlight;
+use backlight::Backlight;
+
+mod buttons;
+use buttons::Button;
+
+use mcu_fourtris::clock;
+use clock::SYSTEM_CLOCK_HZ;
+
+mod controls;
+use controls::{BoosterPack, UartRemote};
+
+use mcu_fourtris::crc;
+
+mod delay;
+use delay::Delay;
+
+use mcu_fourtris::eeprom;
+mod eeprom_driver;
+use eeprom_driver::Eeprom;
+
+use mcu_fourtris::entropy;
+use entropy::{Entropy, Source as EntropySource};
+
+mod flash_driver;
+use flash_driver::Flash;
+
+mod font;
+
+use mcu_fourtris::highscore;
+use highscore::{Entry, EntryStatus, HighScores, InitialsEntry};
+
+use mcu_fourtris::input;
+use input::{anything_held, InputSource, Presses};
+
 mod lcd_backend;
-use lcd_backend::{Lcd, LcdBackend};
+use lcd_backend::{Lcd, LcdBackend, RESTART_BAR_WIDTH};
 
-mod randy;
+use mcu_fourtris::lines;
+use lines::LineCounter;
+
+mod menu;
+use menu::{MenuStatus, SettingsMenu};
+
+use mcu_fourtris::music;
+
+mod pll;
+
+mod power;
+use power::SleepTimer;
+
+mod profiler;
+use profiler::{Phase, Profiler};
+
+use mcu_fourtris::randomizer;
+use randomizer::Randomizer;
+
+use mcu_fourtris::randy;
 use randy::Randy;
 
+mod restart;
+use restart::{HoldStatus, RestartHold};
+
+use mcu_fourtris::seed;
+use seed::{PickerStatus, SeedPicker};
+
+use mcu_fourtris::settings;
+use settings::{ControlScheme, Settings};
+
+use mcu_fourtris::speed;
+use speed::Speed;
+
+use mcu_fourtris::stats;
+use stats::{GameStats, LifetimeStats};
+
+use mcu_fourtris::suspend;
+use suspend::{GameLog, Summary};
+
+mod tilt;
+use tilt::Tilt;
+
 mod timer;
 use timer::Timer0;
 
+mod uart;
+use uart::Uart0;
+
 
-// initialize the ADCs for reading the XY values for the joystick.
-fn initialize_adcs(p: &tm4c123x::Peripherals) {
-    // 1. enable both ADC modules. One will be used to sample the X axis and one will be used to
-    //    sample the y-axis
-    p.SYSCTL.rcgcadc.modify(|r, w| unsafe { w.bits( r.bits() | 3 ) } );
-    // 2. enable clock for the B and D ports                      DCBA
-    p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits (r.bits() | 0b1010) } );
-
-    // wait for port B and D to be ready for use
-    while p.SYSCTL.prgpio.read().bits() & 0b1010 != 0b1010 {}
-
-    // 3. set AFSEL bits for PB5 and PD3                               54 3210
-    p.GPIO_PORTB_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b10_0000 ) }  );
-    //                                                                 3210
-    p.GPIO_PORTD_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b1000 ) }  );
-    // 4. configure PB5 and PD3 as analog by clearing GPIODEN bits
-    p.GPIO_PORTB_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() & (!0b10_0000) ) } );
-    p.GPIO_PORTD_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1000) ) } );
-    // 5. disable analog isolation circuit for the ADC input pins (PB5 and PD3)
-    p.GPIO_PORTB_AHB.amsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b10_0000 ) }  );
-    p.GPIO_PORTD_AHB.amsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b1000 ) }  );
-    // 7. disable sample sequencer 0 in both ADCs
-    // we will sample a different axis on both
-    p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() & (!1) ) } );
-    p.ADC1.actss.modify(|r, w| unsafe { w.bits( r.bits() & (!1) ) } );
-    // 8. configure trigger event for the sample sequencer
-    //    we are using ss0, and will use continuous sampling
-    p.ADC0.emux.modify(|r, w| unsafe { w.bits( r.bits() | 0xF ) } );
-    p.ADC1.emux.modify(|r, w| unsafe { w.bits( r.bits() | 0xF ) } );
-    // 10. configure input source
-    //     ADC0 will sample from PB5 (AIN 11)
-    //     ADC1 will sample from PD3 (AIN 4)
-    p.ADC0.ssmux0.modify(|r, w| unsafe { w.bits( (r.bits() & (!0xF)) | 11) } );
-    p.ADC1.ssmux0.modify(|r, w| unsafe { w.bits( (r.bits() & (!0xF)) | 4) } );
-    // 11. configure the sample control bits in the control registers
-    //     0010
-    //     read from pin, no interrupt, end of sequence, not differential input
-    //     only one sample from the sequence
-    p.ADC0.ssctl0.write(|w| unsafe { w.bits(2) });
-    p.ADC1.ssctl0.write(|w| unsafe { w.bits(2) });
-    // 13. enable sample sequencer 0 on both ADCs
-    p.ADC0.actss.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) } );
-    p.ADC1.actss.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) } );
-
-    // wait for the ADCs to be ready
-    while p.SYSCTL.pradc.read().bits() & 0b11 != 0b11 {}
+// Initialize the random number generator
+// Timer0 needs to be running so there's some jitter to pick up
+fn initialize_rng(rng: &mut Randy, entropy: &mut Entropy, adc: &Adc, timer0: &Timer0) {
+    // don't hang forever if every noise source is broken, Randy has a PRNG to fall back on
+    for _ in 0..INITIAL_RNG_SAMPLES {
+        if rng.nums_available() == rng.capacity() {
+            break;
+        }
+        let snapshot = adc.sample();
+        let joystick = (snapshot.joystick_x ^ snapshot.joystick_y) & 1;
+        if let Some(bit) = entropy.harvest(Some(joystick as usize),
+                                           (snapshot.temperature & 1) as usize,
+                                           (timer0.value() & 1) as usize) {
+            rng.add_bit(bit);
+        }
+    }
 }
 
-// Configure PD6, PD7, and PE4 as inputs for the push buttons and joystick
-fn initialize_buttons(p: &tm4c123x::Peripherals) {
-    // --------------------------------------------------
-    // initialize GPIO pins for push buttons and joystick
-    // These buttons pull the pins to ground
-    // there are pull-up resistors on the pcb
-    // --------------------------------------------------
-    // 1. enable clock for ports D and E (bits 3 and 4)                     E DBCA
-    p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | 0b1_1000 )});
-
-    // wait for ports D and E to be ready
-    while p.SYSCTL.prgpio.read().bits() & 0b1_1000 != 0b1_1000 {}
-    // unlock the GPIOCR register, and modify it so we can configure PD7
-    p.GPIO_PORTD_AHB.lock.write(|w| unsafe { w.bits( 0x4C4F434B ) });
-    p.GPIO_PORTD_AHB.cr.modify(|r, w| unsafe { w.bits( r.bits() | 0x80 ) });
-    // 2. set pins D6 and D7 as input                                            7654 3210
-    p.GPIO_PORTD_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1100_0000) )});
-    p.GPIO_PORTD_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() |   0b1100_0000)});
-    // 3. set pin E4 as input                                                    4 3210
-    p.GPIO_PORTE_AHB.dir.modify(|r, w| unsafe { w.bits( r.bits() & (!0b1_0000) )});
-    p.GPIO_PORTE_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0b1_0000 )});
+/// Tilt controls, if the settings call for them
+fn tilt_controls(p: &tm4c123x::Peripherals, settings: &Settings) -> Option<Tilt> {
+    match settings.control_scheme {
+        ControlScheme::Tilt => Some(Tilt::new(BoosterPack::new(p, settings), settings.tilt_sensitivity)),
+        ControlScheme::Joystick => None,
+    }
 }
 
-// Initialize the random number generator
-fn initialize_rng(rng: &mut Randy, p: &tm4c123x::Peripherals) {
-    while rng.nums_available() < rng.capacity() {
-        // wait for data to be available (X-axis)
-        while p.ADC0.ssfstat0.read().bits() & 0x100 != 0 {}
-        // wait for data to be available (Y-axis)
-        while p.ADC1.ssfstat0.read().bits() & 0x100 != 0 {}
+/// Save the settings, if the EEPROM is working
+fn save_settings(eeprom: Option<&Eeprom>, settings: &Settings, uart: &Uart0) {
+    if let Some(eeprom) = eeprom {
+        if settings.save(eeprom).is_err() {
+            uart.write_bytes(b"eeprom: couldn't save the settings\r\n");
+        }
+    }
+}
+
+/// Save the game in progress to flash, if it changed since the last time
+fn save_game(flash: &Flash, log: &mut GameLog, lcd_backend: &LcdBackend, uart: &Uart0) {
+    if log.has_unsaved() && !log.is_full() {
+        let summary = Summary { score: lcd_backend.score(), level: lcd_backend.level() };
+        if log.save(flash, summary).is_err() {
+            uart.write_bytes(b"flash: couldn't save the game\r\n");
+        }
+    }
+    if log.is_full() {
+        // too long to save, an older save of it would just be confusing
+        discard_game(flash, uart);
+    }
+}
 
-        let x_data = p.ADC0.ssfifo0.read().bits() & 1;
-        let y_data = p.ADC1.ssfifo0.read().bits() & 1;
+/// Get rid of the saved game, once it can't be continued anymore
+fn discard_game(flash: &Flash, uart: &Uart0) {
+    if suspend::discard(flash).is_err() {
+        uart.write_bytes(b"flash: couldn't erase the saved game\r\n");
+    }
+}
 
-        rng.add_bit((x_data ^ y_data) as usize);
+/// Add the game that just ended to the lifetime stats and save them. Returns the game's stats,
+/// and its piece counts go out over the UART for checking up on the randomizer.
+fn finish_game(lifetime: &mut LifetimeStats, game_stats: &mut GameStats, lines: u32, level: usize,
+               eeprom: Option<&Eeprom>, uart: &Uart0) -> GameStats {
+    lifetime.add_game(game_stats, lines, level);
+    if let Some(eeprom) = eeprom {
+        if lifetime.save(eeprom).is_err() {
+            uart.write_bytes(b"eeprom: couldn't save the stats\r\n");
+        }
     }
+
+    uart.write_bytes(b"pieces:");
+    for (name, count) in stats::PIECE_NAMES.iter().zip(game_stats.pieces.iter()) {
+        uart.write_bytes(&[b' ', *name, b' ']);
+        uart.write_decimal(*count);
+    }
+    uart.write_bytes(b"\r\n");
+
+    let finished = *game_stats;
+    game_stats.reset();
+    finished
+}
+
+/// One row of the settings menu
+fn draw_menu_row(lcd_backend: &mut LcdBackend, menu: &SettingsMenu, row: usize) {
+    let item = menu::ITEMS[row];
+    lcd_backend.draw_menu_row(row, item.label(), item.value(menu.settings()), row == menu.cursor());
 }
 
+/// Upper limit on the number of ADC samples taken to fill up Randy at boot
+const INITIAL_RNG_SAMPLES : usize = 4096;
+
+/// How often the profiler reports, in seconds. It counts frames, so the frame rate decides how many.
+const PROFILE_REPORT_SECONDS : u32 = 2;
+
 #[entry]
 fn main() -> ! {
     let peripherals = tm4c123x::Peripherals::take().unwrap();
+    let mut core_peripherals = cortex_m::Peripherals::take().unwrap();
+
+    // crank the clock up to 80 MHz before anything else works out its timing
+    pll::initialize(&peripherals);
 
     // set ports A, B, D, E and F to use the fast GPIO bus
     // 0b100011
@@ -116,105 +209,526 @@ fn main() -> ! {
     peripherals.SYSCTL.gpiohbctl.modify(|r, w| unsafe { w.bits (r.bits() | 0b11_1011) } );
 
 
-    initialize_adcs(&peripherals);
-    initialize_buttons(&peripherals);
+    let adc = Adc::new(&peripherals);
+    buttons::initialize(&peripherals);
+
+    // settings and high scores are kept in the EEPROM, if it works
+    let eeprom = Eeprom::new(&peripherals);
+    // and a game in progress goes in the flash, it's too big for the EEPROM
+    let flash = Flash::new(&peripherals);
+    let mut settings =
+        match eeprom.as_ref() {
+            Some(eeprom) => Settings::load(eeprom),
+            None => Default::default(),
+        };
 
-    let mut rng = Randy::new();
-    initialize_rng(&mut rng, &peripherals);
+    // buttons held while booting change the settings
+    let boot_snapshot = adc.sample();
+    settings.apply_boot_controls(buttons::is_pressed(&peripherals, Button::S1),
+                                 buttons::is_pressed(&peripherals, Button::S2),
+                                 buttons::is_pressed(&peripherals, Button::Select),
+                                 boot_snapshot.joystick_x,
+                                 boot_snapshot.joystick_y);
 
     // CONFIGURE THE TIMER!
-    // want to run at 60fps
-    // clock is 16 MHz
-    // timer value = 16_000_000 / 60 ~ 266_667
-    // eh, turns out 70 fps is more fun! :)
-    let timer0 = Timer0::new(&peripherals, 16000000 / 70);
+    // timer value = clock / fps, e.g. 80_000_000 / 70 ~ 1_142_857
+    let mut frame_ticks = SYSTEM_CLOCK_HZ / settings.frame_rate.hz();
+    let timer0 = Timer0::new(&peripherals, frame_ticks);
+    timer0.start();
+    // the button interrupts timestamp their events with Timer0, so they have to wait for it
+    buttons::enable_interrupts(&peripherals);
+
+    // Timer0 counts clock cycles, so a frame lasts frame_ticks cycles
+    let mut profiler = Profiler::new(&mut core_peripherals.DCB, &mut core_peripherals.DWT, frame_ticks);
+
+    let mut rng = Randomizer::new(Randy::new(), settings.randomizer);
+    let mut entropy = Entropy::new();
+    initialize_rng(rng.source_mut(), &mut entropy, &adc, &timer0);
+
+    // sample the joystick and friends at the start of every frame
+    timer0.enable_adc_trigger();
+    adc.start_timer_trigger();
+
+    // the joystick button needs to be held for 1.5 s before the game restarts
+    let mut restart_hold = RestartHold::new(SYSTEM_CLOCK_HZ / 2 * 3);
 
     // initialize the LCD
-    let mut lcd_backend = LcdBackend::new(Lcd::new(&peripherals));
+    let mut lcd_backend = LcdBackend::new(Lcd::new(&peripherals, Delay::new(core_peripherals.SYST)));
+    lcd_backend.set_palette(settings.palette);
 
 
     let mut game = Game::new(&mut rng);
     let mut input : Input = Default::default();
     let mut state = GameState::Playing;
+    let mut paused = false;
+    let mut speed = Speed::new();
+    let mut presses : Presses = Default::default();
+
+    let mut booster_pack = BoosterPack::new(&peripherals, &settings);
+    let mut tilt = tilt_controls(&peripherals, &settings);
+    let uart = Uart0::new(&peripherals);
+    let mut remote = UartRemote::new(&uart);
+
+    let mut high_scores =
+        match eeprom.as_ref() {
+            Some(eeprom) => HighScores::load(eeprom),
+            None => {
+                uart.write_bytes(b"eeprom: not working, settings and high scores won't be saved\r\n");
+                HighScores::new()
+            },
+        };
+    let mut lines = LineCounter::new();
+    let mut lifetime =
+        match eeprom.as_ref() {
+            Some(eeprom) => LifetimeStats::load(eeprom),
+            None => LifetimeStats::new(),
+        };
+    let mut game_stats = GameStats::new();
+    let mut last_game = GameStats::new();
+    let mut stats_screen = false;
+    let mut initials : Option<InitialsEntry> = None;
+    let mut menu : Option<SettingsMenu> = None;
+    let mut last_initials = *b"AAA";
+    // everything the game got from the rng and the player, so it can be saved and replayed
+    let mut log = GameLog::new();
+
+    // everything starts on the title screen, the game screen shows up with the first game.
+    // If a game was saved before the power went out, the player gets to continue it first.
+    let saved_game = suspend::saved_game(&flash);
+    let mut continue_screen = saved_game.is_some();
+    let mut title = !continue_screen;
+    lcd_backend.draw_frame_rate(settings.frame_rate.hz());
+    match saved_game {
+        Some(summary) => lcd_backend.draw_continue_screen(summary.score, summary.level),
+        None => lcd_backend.draw_title(high_scores.entries(), None),
+    }
+
+    // in seed mode the player picks a seed before every game, start them off with a random one
+    let mut picker : Option<SeedPicker> = None;
+    let mut last_seed = rng.source_mut().next_u32();
 
-    lcd_backend.draw_initial_screen();
     lcd_backend.turn_on_display();
-    
-    timer0.start();
+    let mut backlight = Backlight::new(&peripherals, settings.brightness);
+    let audio = Audio::new(&peripherals);
+
+    let mut sleep_timer = SleepTimer::new();
+    // after waking up, the button that did it is ignored until it's let go
+    let mut waking = false;
+
+    let mut entropy_source = entropy.preferred_source();
+    // catch up on the frames that went by while booting, so they don't count as late
+    timer0.wait_for_next_frame();
     loop {
-        // Check to see if the user wants to restart the game
-        let porte = peripherals.GPIO_PORTE_AHB.data.read().bits();
+        profiler.enter(Phase::Idle);
+        if settings.profile && profiler.frames() >= PROFILE_REPORT_SECONDS * settings.frame_rate.hz() {
+            profiler.report(&uart, buttons::take_worst_latency());
+            lcd_backend.draw_load(profiler.worst_load());
+            profiler.reset();
+        }
+
+        // chill out until the next frame starts
+        let elapsed_frames = timer0.wait_for_next_frame();
+        profiler.enter(Phase::Input);
+        if elapsed_frames > 1 {
+            // the last frame ran long, the profiler says how often
+            profiler.add_missed(elapsed_frames - 1);
+        }
+
+        // wait for the analog readings taken at the start of this frame. Every frame, whoever is
+        // in charge, or the temperature noise would go stale.
+        let snapshot = adc.snapshot();
+        booster_pack.set_snapshot(snapshot);
+        if let Some(tilt) = tilt.as_mut() {
+            tilt.set_snapshot(snapshot);
+        }
+
+        // the UART remote takes over as soon as it sends something
+        let source : &mut dyn InputSource =
+            if remote.is_connected() {
+                &mut remote
+            } else if let Some(tilt) = tilt.as_mut() {
+                tilt
+            } else {
+                &mut booster_pack
+            };
+
+        // get input
+        let actions = source.poll(&mut input);
+        let pressed = presses.update(&input);
+
+        // the backlight dims when nobody is playing
+        let active = anything_held(&input) || actions.pause || actions.restart;
+        backlight.update(active, frame_ticks * elapsed_frames);
+
+        // and after a few minutes everything goes to sleep
+        if sleep_timer.update(active, frame_ticks * elapsed_frames) {
+            // the power might go out while sleeping
+            save_game(&flash, &mut log, &lcd_backend, &uart);
+            // the sequencer's interrupt would wake us right back up
+            audio.stop();
+            backlight.off();
+            lcd_backend.sleep();
+            timer0.stop();
+            power::deep_sleep(&peripherals, &mut core_peripherals.SCB);
+            timer0.start();
+            lcd_backend.wake();
+
+            // don't drop somebody straight back into a running game
+            if let GameState::Playing = state {
+                paused = true;
+            }
+            sleep_timer.reset();
+            profiler.reset();
+            waking = true;
+            continue;
+        }
+        if waking {
+            // holding (or just letting go of) the wake up button does nothing
+            waking = active;
+            continue;
+        }
+
+        // use noise to get some more "random" bits
+        if let Some(bit) = entropy.harvest(source.noise_bit(),
+                                           (snapshot.temperature & 1) as usize,
+                                           (timer0.value() & 1) as usize) {
+            rng.source_mut().add_bit(bit);
+        }
 
-        let reset_selected = porte & 0x10 == 0x00;
+        // let whoever is listening know when a noise source goes bad (or comes back)
+        if entropy.preferred_source() != entropy_source {
+            entropy_source = entropy.preferred_source();
+            uart.write_bytes(
+                match entropy_source {
+                    Some(EntropySource::Joystick) => b"entropy: using joystick\r\n",
+                    Some(EntropySource::Temperature) => b"entropy: using temperature sensor\r\n",
+                    Some(EntropySource::TimerJitter) => b"entropy: using timer jitter\r\n",
+                    None => b"entropy: every source failed, PRNG only\r\n",
+                });
+        }
 
-        // joystick select resets the game
-        if reset_selected {
-            game = Game::new(&mut rng);
+        // holding the restart button resets the game, and on the title screen a tap starts one
+        let mut new_game = title && actions.pause;
+        match restart_hold.update(actions.restart, frame_ticks * elapsed_frames) {
+            HoldStatus::Holding => {
+                lcd_backend.draw_restart_progress(restart_hold.progress(RESTART_BAR_WIDTH));
+            },
+            HoldStatus::Cancelled => {
+                lcd_backend.clear_restart_progress();
+            },
+            HoldStatus::Restart => {
+                new_game = true;
+            },
+            HoldStatus::Idle => {},
+        }
+        if new_game {
+            // a game that got restarted still counts
+            if game_stats.started() {
+                last_game = finish_game(&mut lifetime, &mut game_stats, lines.lines(), lcd_backend.level(),
+                                        eeprom.as_ref(), &uart);
+            }
+            rng.reset();
+            log.clear();
+            game = Game::new(&mut log.recorder(&mut game_stats.counter(&mut rng)));
+            // the music starts over with the game
+            audio.stop_music();
+            discard_game(&flash, &uart);
+            continue_screen = false;
             // clear screen
-            lcd_backend.clear_playing_field();
-            lcd_backend.draw_score(0);
-            lcd_backend.draw_level(1);
+            lcd_backend.new_game();
+            lines.reset();
             state = GameState::Playing;
+            paused = false;
+            speed.reset();
+            title = false;
+            initials = None;
+            menu = None;
+            stats_screen = false;
+            if settings.seed_mode {
+                // back to picking a seed, the last one is a good place to start.
+                // The game gets made again once there is one, there's nothing to save until then.
+                log.clear();
+                game_stats.reset();
+                rng.source_mut().unseed();
+                let new_picker = SeedPicker::new(last_seed);
+                lcd_backend.draw_seed(new_picker.seed(), Some(new_picker.cursor()));
+                picker = Some(new_picker);
+            }
+            // the tap that started the game doesn't count as a pause
+            continue;
         }
 
-        // Check ADC data, and use it to get some more "random" bits
-        let mut random_bit = 0;
-        // get input
-        // see if there is a sample ready for ADC0 - X axis
-        let horizontal_data_available = peripherals.ADC0.ssfstat0.read().bits() & 0x100 == 0;
-        if horizontal_data_available {
-            // reset horizontal inputs
-            input.left = false;
-            input.right = false;
-
-            let horizontal_reading = peripherals.ADC0.ssfifo0.read().bits();
-            if horizontal_reading < 50 {
-                input.left = true;
-            } else if horizontal_reading > 4000 {
-                input.right = true;
+        // on the continue screen a tap picks the saved game back up, and S1 forgets about it
+        if continue_screen {
+            if actions.pause {
+                if log.load(&flash) {
+                    // play the whole game over again without showing it, then put it on the screen
+                    rng.reset();
+                    lcd_backend.begin_restore();
+                    lines.reset();
+                    game_stats.reset();
+                    let mut deals = suspend::saved_deals(&flash);
+                    game = Game::new(&mut game_stats.counter(&mut deals));
+                    for input in suspend::saved_inputs(&flash) {
+                        state = game.run_loop(&input, &mut game_stats.counter(&mut deals));
+                        game_stats.add_time(SYSTEM_CLOCK_HZ / speed::ticks_per_second(lcd_backend.level()));
+                        game.draw(&mut lcd_backend);
+                        lines.update(lcd_backend.filled_blocks());
+                    }
+                    lcd_backend.end_restore();
+
+                    // give the player a moment before it gets going again
+                    paused = true;
+                    speed.reset();
+                    profiler.reset();
+                } else {
+                    uart.write_bytes(b"flash: couldn't load the saved game\r\n");
+                    lcd_backend.draw_title(high_scores.entries(), None);
+                    title = true;
+                }
+                continue_screen = false;
+            } else if pressed.cw_rotate {
+                discard_game(&flash, &uart);
+                lcd_backend.draw_title(high_scores.entries(), None);
+                continue_screen = false;
+                title = true;
+            }
+            continue;
+        }
+
+        // tapping anything on the stats screen goes back to the title screen
+        if stats_screen {
+            if actions.pause || pressed.cw_rotate || pressed.ccw_rotate || pressed.down {
+                lcd_backend.draw_title(high_scores.entries(), None);
+                stats_screen = false;
+                title = true;
             }
+            continue;
+        }
 
-            random_bit = horizontal_reading;
+        // S1 or S2 on the title screen opens the settings menu, joystick down shows the stats
+        if title {
+            if pressed.down {
+                lcd_backend.draw_stats_screen(&lifetime, &last_game);
+                stats_screen = true;
+                title = false;
+            } else if pressed.cw_rotate || pressed.ccw_rotate {
+                let settings_menu = SettingsMenu::new(settings);
+                lcd_backend.draw_menu_screen();
+                for row in 0..menu::ITEMS.len() {
+                    draw_menu_row(&mut lcd_backend, &settings_menu, row);
+                }
+                menu = Some(settings_menu);
+                title = false;
+            }
+            continue;
         }
 
-        // see if there is a sample ready for ADC1 - Y axis
-        let vertical_data_available = peripherals.ADC1.ssfstat0.read().bits() & 0x100 == 0;
-        if vertical_data_available {
-            let vertical_reading = peripherals.ADC1.ssfifo0.read().bits();
-            input.down = vertical_reading < 10;
+        if let Some(settings_menu) = menu.as_mut() {
+            match settings_menu.update(&pressed, actions.pause) {
+                MenuStatus::Moved { from } => {
+                    draw_menu_row(&mut lcd_backend, settings_menu, from);
+                    draw_menu_row(&mut lcd_backend, settings_menu, settings_menu.cursor());
+                },
+                MenuStatus::Changed => {
+                    draw_menu_row(&mut lcd_backend, settings_menu, settings_menu.cursor());
+                },
+                MenuStatus::Done => {
+                    settings = *settings_menu.settings();
+                    save_settings(eeprom.as_ref(), &settings, &uart);
+
+                    // put everything to work
+                    frame_ticks = SYSTEM_CLOCK_HZ / settings.frame_rate.hz();
+                    timer0.set_interval(frame_ticks);
+                    profiler.set_budget(frame_ticks);
+                    lcd_backend.draw_frame_rate(settings.frame_rate.hz());
+                    lcd_backend.set_palette(settings.palette);
+                    backlight.set_brightness(settings.brightness);
+                    rng.set_strategy(settings.randomizer);
+                    booster_pack = BoosterPack::new(&peripherals, &settings);
+                    tilt = tilt_controls(&peripherals, &settings);
+
+                    lcd_backend.draw_title(high_scores.entries(), None);
+                    menu = None;
+                    title = true;
+                },
+                MenuStatus::Unchanged => {},
+            }
+            continue;
+        }
 
-            random_bit ^= vertical_reading;
+        if let Some(entry) = initials.as_mut() {
+            match entry.update(&pressed, actions.pause) {
+                EntryStatus::Changed => {
+                    lcd_backend.draw_initials(&entry.initials(), entry.cursor());
+                },
+                EntryStatus::Done => {
+                    last_initials = entry.initials();
+                    let level = lcd_backend.level();
+                    let rank = high_scores.insert(Entry {
+                        initials: last_initials,
+                        score: lcd_backend.score(),
+                        level: if level > 255 { 255 } else { level as u8 },
+                        lines: lines.lines(),
+                    });
+                    if let Some(eeprom) = eeprom.as_ref() {
+                        if high_scores.save(eeprom).is_err() {
+                            uart.write_bytes(b"eeprom: couldn't save the high scores\r\n");
+                        }
+                    }
+                    lcd_backend.draw_title(high_scores.entries(), rank);
+                    initials = None;
+                    title = true;
+                },
+                EntryStatus::Unchanged => {},
+            }
+            continue;
         }
 
-        // use ADC readings to generate a "random" bit
-        // and add it to our random number generator
-        if horizontal_data_available && vertical_data_available {
-            rng.add_bit((random_bit & 1) as usize);
+        if let Some(seed_picker) = picker.as_mut() {
+            match seed_picker.update(&pressed) {
+                PickerStatus::Changed => {
+                    lcd_backend.draw_seed(seed_picker.seed(), Some(seed_picker.cursor()));
+                },
+                PickerStatus::Roll => {
+                    seed_picker.set_seed(rng.source_mut().next_u32());
+                    lcd_backend.draw_seed(seed_picker.seed(), Some(seed_picker.cursor()));
+                },
+                PickerStatus::Unchanged => {},
+            }
+
+            // tapping the joystick button starts the game
+            if actions.pause {
+                last_seed = seed_picker.seed();
+                picker = None;
+
+                // everything that decides the pieces has to start from scratch
+                rng.source_mut().seed(last_seed);
+                rng.reset();
+                game = Game::new(&mut log.recorder(&mut game_stats.counter(&mut rng)));
+                speed.reset();
+                lcd_backend.draw_seed(last_seed, None);
+
+                // so the seed can be shared, or used to replay the game on a PC
+                uart.write_bytes(b"seed: ");
+                uart.write_bytes(&seed::seed_text(last_seed));
+                uart.write_bytes(b"\r\n");
+            }
+
+            // no game until there's a seed
+            continue;
         }
 
-        // button 1 (PD6) is for counter clockwise rotation
-        // button 2 (PD7) is for counter clockwise rotation
-        let portd = peripherals.GPIO_PORTD_AHB.data.read().bits();
-        
-        let ccw_rotate = portd & 0b1000_0000 == 0; 
-        let cw_rotate  = portd & 0b0100_0000 == 0;
+        if actions.pause {
+            paused = !paused;
+            // nobody's watching, good time to save in case the power goes out
+            if paused {
+                save_game(&flash, &mut log, &lcd_backend, &uart);
+            }
+        }
+        // nothing moves on the pause screen, so the LCD can take it easy
+        lcd_backend.set_power_saving(paused && matches!(state, GameState::Playing));
 
-        input.ccw_rotate = ccw_rotate;
-        input.cw_rotate = cw_rotate;
+        // the music plays along with the game, and waits for it while it's paused
+        match state {
+            GameState::Playing if paused => audio.pause_music(),
+            GameState::Playing => audio.play_music(lcd_backend.level()),
+            GameState::GameOver => audio.stop_music(),
+        }
 
         match state {
-            GameState::Playing => {
-                // process input
-                state = game.run_loop(&input, &mut rng);
+            GameState::Playing if !paused => {
+                // process input, as many times as the level's speed calls for
+                profiler.enter(Phase::Logic);
+                let level = lcd_backend.level();
+                let dealt = game_stats.dealt();
+                for _ in 0..speed.ticks(lcd_backend.level(), settings.frame_rate.hz()) {
+                    state = game.run_loop(&input, &mut log.recorder(&mut game_stats.counter(&mut rng)));
+                    log.record_input(&input);
+                    if let GameState::GameOver = state {
+                        break;
+                    }
+                }
                 // draw to the screen
+                profiler.enter(Phase::Draw);
                 game.draw(&mut lcd_backend);
+                let cleared = lines.update(lcd_backend.filled_blocks());
+                game_stats.add_time(frame_ticks * elapsed_frames);
+                // the log only has room in RAM for a minute or so, the rest goes to flash
+                if log.wants_save() {
+                    save_game(&flash, &mut log, &lcd_backend, &uart);
+                }
+
+                // sound effects, only the most important thing that happened this frame.
+                // A new piece means the last one locked.
+                if let GameState::GameOver = state {
+                    audio.stop_music();
+                    audio.play(Effect::GameOver);
+                } else if lcd_backend.level() > level {
+                    audio.play(Effect::LevelUp);
+                } else if cleared > 0 {
+                    audio.play(Effect::lines(cleared));
+                } else if game_stats.dealt() > dealt {
+                    audio.play(Effect::Lock);
+                } else if pressed.cw_rotate || pressed.ccw_rotate {
+                    audio.play(Effect::Rotate);
+                } else if pressed.left || pressed.right {
+                    audio.play(Effect::Move);
+                }
+
+                if let GameState::GameOver = state {
+                    last_game = finish_game(&mut lifetime, &mut game_stats, lines.lines(), lcd_backend.level(),
+                                            eeprom.as_ref(), &uart);
+
+                    // nothing left to continue
+                    log.clear();
+                    discard_game(&flash, &uart);
+
+                    // a good enough score gets a spot on the title screen
+                    if high_scores.qualifies(lcd_backend.score()) {
+                        let entry = InitialsEntry::new(last_initials);
+                        lcd_backend.draw_initials_screen(lcd_backend.score());
+                        lcd_backend.draw_initials(&entry.initials(), entry.cursor());
+                        initials = Some(entry);
+                    }
+                }
+            },
+            GameState::GameOver if actions.pause => {
+                // tapping the button after a game over goes back to the title screen
+                lcd_backend.draw_title(high_scores.entries(), None);
+                title = true;
             },
-            GameState::GameOver => {
+            _ => {
+                // paused or game over, joystick left/right changes the frame rate
+                let frame_rate =
+                    if pressed.right {
+                        settings.frame_rate.faster()
+                    } else if pressed.left {
+                        settings.frame_rate.slower()
+                    } else {
+                        settings.frame_rate
+                    };
+                if frame_rate != settings.frame_rate {
+                    settings.frame_rate = frame_rate;
+                    frame_ticks = SYSTEM_CLOCK_HZ / frame_rate.hz();
+                    timer0.set_interval(frame_ticks);
+                    profiler.set_budget(frame_ticks);
+                    lcd_backend.draw_frame_rate(frame_rate.hz());
+                    save_settings(eeprom.as_ref(), &settings, &uart);
+                }
+
+                // joystick down cycles through the backlight brightness presets
+                if pressed.down {
+                    settings.next_brightness();
+                    backlight.set_brightness(settings.brightness);
+                    save_settings(eeprom.as_ref(), &settings, &uart);
+                }
+
+                // S1 and S2 together reset the LCD and redraw everything, in case it got scrambled
+                if input.cw_rotate && input.ccw_rotate && (pressed.cw_rotate || pressed.ccw_rotate) {
+                    lcd_backend.recover();
+                }
             },
         }
-
-        // chill out until a timer interrupt occurs
-        while !timer0.timeout_occured() {}
-        timer0.clear_interrupt();
     }
 }
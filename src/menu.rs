@@ -0,0 +1,196 @@
+use fourtris::game::Input;
+
+use crate::randomizer::Strategy;
+use crate::settings::{ControlScheme, FrameRate, Palette, Sensitivity, Settings};
+
+// The settings menu, opened from the title screen:
+//  - joystick down: next setting (wraps around to the top)
+//  - joystick left/right: change the setting
+//  - joystick button: save and go back to the title screen
+// Changes are made to a copy of the settings, which only replaces the real ones when the menu is done.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Item {
+    Controls,
+    Tilt,
+    Joystick,
+    Buttons,
+    Randomizer,
+    SeedMode,
+    FrameRate,
+    Brightness,
+    Colors,
+}
+
+/// Everything in the menu, top to bottom
+pub const ITEMS : [Item; 9] = [
+    Item::Controls,
+    Item::Tilt,
+    Item::Joystick,
+    Item::Buttons,
+    Item::Randomizer,
+    Item::SeedMode,
+    Item::FrameRate,
+    Item::Brightness,
+    Item::Colors,
+];
+
+const SENSITIVITIES : [Sensitivity; 3] = [Sensitivity::Low, Sensitivity::Medium, Sensitivity::High];
+const RANDOMIZERS : [Strategy; 3] = [Strategy::Uniform, Strategy::Bag, Strategy::History { rerolls: 4 }];
+const FRAME_RATES : [FrameRate; 3] = [FrameRate::Fps50, FrameRate::Fps60, FrameRate::Fps70];
+const BRIGHTNESSES : [u8; 4] = [25, 50, 75, 100];
+
+impl Item {
+    pub fn label(self) -> &'static [u8] {
+        match self {
+            Item::Controls => b"CONTROLS",
+            Item::Tilt => b"TILT",
+            Item::Joystick => b"JOYSTICK",
+            Item::Buttons => b"BUTTONS",
+            Item::Randomizer => b"RANDOMIZER",
+            Item::SeedMode => b"SEED MODE",
+            Item::FrameRate => b"FRAME RATE",
+            Item::Brightness => b"BRIGHTNESS",
+            Item::Colors => b"COLORS",
+        }
+    }
+
+    /// What the setting is set to, as text
+    pub fn value(self, settings: &Settings) -> &'static [u8] {
+        let sensitivity = |sensitivity: Sensitivity| {
+            match sensitivity {
+                Sensitivity::Low => b"LOW" as &[u8],
+                Sensitivity::Medium => b"MEDIUM",
+                Sensitivity::High => b"HIGH",
+            }
+        };
+        let on_off = |on: bool| if on { b"ON" as &[u8] } else { b"OFF" };
+        match self {
+            Item::Controls =>
+                match settings.control_scheme {
+                    ControlScheme::Joystick => b"JOYSTICK",
+                    ControlScheme::Tilt => b"TILT",
+                },
+            Item::Tilt => sensitivity(settings.tilt_sensitivity),
+            Item::Joystick => sensitivity(settings.joystick_sensitivity),
+            Item::Buttons => if settings.swap_rotation { b"S1 CCW" } else { b"S1 CW" },
+            Item::Randomizer =>
+                match settings.randomizer {
+                    Strategy::Uniform => b"UNIFORM",
+                    Strategy::Bag => b"7-BAG",
+                    Strategy::History { .. } => b"HISTORY",
+                },
+            Item::SeedMode => on_off(settings.seed_mode),
+            Item::FrameRate =>
+                match settings.frame_rate {
+                    FrameRate::Fps50 => b"50",
+                    FrameRate::Fps60 => b"60",
+                    FrameRate::Fps70 => b"70",
+                },
+            Item::Brightness =>
+                match settings.brightness {
+                    percent if percent > 75 => b"100",
+                    percent if percent > 50 => b"75",
+                    percent if percent > 25 => b"50",
+                    _ => b"25",
+                },
+            Item::Colors =>
+                match settings.palette {
+                    Palette::Classic => b"CLASSIC",
+                    Palette::Guideline => b"GUIDELINE",
+                },
+        }
+    }
+
+    /// Move the setting on to the next (or previous) option
+    fn change(self, settings: &mut Settings, forward: bool) {
+        match self {
+            Item::Controls => {
+                settings.control_scheme =
+                    match settings.control_scheme {
+                        ControlScheme::Joystick => ControlScheme::Tilt,
+                        ControlScheme::Tilt => ControlScheme::Joystick,
+                    };
+            },
+            Item::Tilt => settings.tilt_sensitivity = cycle(&SENSITIVITIES, settings.tilt_sensitivity, forward),
+            Item::Joystick => {
+                settings.joystick_sensitivity = cycle(&SENSITIVITIES, settings.joystick_sensitivity, forward);
+            },
+            Item::Buttons => settings.swap_rotation = !settings.swap_rotation,
+            Item::Randomizer => settings.randomizer = cycle(&RANDOMIZERS, settings.randomizer, forward),
+            Item::SeedMode => settings.seed_mode = !settings.seed_mode,
+            Item::FrameRate => settings.frame_rate = cycle(&FRAME_RATES, settings.frame_rate, forward),
+            Item::Brightness => settings.brightness = cycle(&BRIGHTNESSES, settings.brightness, forward),
+            Item::Colors => {
+                settings.palette =
+                    match settings.palette {
+                        Palette::Classic => Palette::Guideline,
+                        Palette::Guideline => Palette::Classic,
+                    };
+            },
+        }
+    }
+}
+
+/// The option after (or before) `current`, wrapping around at the ends.
+/// Something that isn't one of the options goes back to the first one.
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T, forward: bool) -> T {
+    match options.iter().position(|option| *option == current) {
+        Some(i) if forward => options[(i + 1) % options.len()],
+        Some(i) => options[(i + options.len() - 1) % options.len()],
+        None => options[0],
+    }
+}
+
+/// What the menu wants after handling a frame of input
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuStatus {
+    Unchanged,
+    /// the cursor moved off of the row `from`, both rows need to be redrawn
+    Moved { from: usize },
+    /// the setting under the cursor changed
+    Changed,
+    /// time to save
+    Done,
+}
+
+pub struct SettingsMenu {
+    /// index into ITEMS
+    cursor: usize,
+    settings: Settings,
+}
+
+impl SettingsMenu {
+    pub fn new(settings: Settings) -> SettingsMenu {
+        SettingsMenu {
+            cursor: 0,
+            settings,
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The settings with all the changes made so far
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// `pressed` should only have what was pressed this frame (see input::Presses),
+    /// `done` is the joystick button being tapped
+    pub fn update(&mut self, pressed: &Input, done: bool) -> MenuStatus {
+        if done {
+            MenuStatus::Done
+        } else if pressed.down {
+            let from = self.cursor;
+            self.cursor = (self.cursor + 1) % ITEMS.len();
+            MenuStatus::Moved { from }
+        } else if pressed.left || pressed.right {
+            ITEMS[self.cursor].change(&mut self.settings, pressed.right);
+            MenuStatus::Changed
+        } else {
+            MenuStatus::Unchanged
+        }
+    }
+}
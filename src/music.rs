@@ -0,0 +1,252 @@
+// Background music. Songs are tables of one byte notes, kept in flash:
+//   bit:   7 6 5 4 3     2 1 0
+//           PITCH       DURATION
+// PITCH is the number of semitones above B3, so 1 is C4 (middle C) and 31 is F#6. 0 is a rest.
+// DURATION is a code for how many sixteenth notes the note lasts (see DURATIONS).
+//
+// How long a sixteenth lasts depends on the level, the music speeds up along with the game.
+// Every note is cut short by GAP_MS, so repeated notes don't run together.
+
+/// Sixteenth notes for each duration code
+const DURATIONS : [u16; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+// duration codes
+pub const EIGHTH : u8 = 1;
+pub const QUARTER : u8 = 3;
+pub const DOTTED_QUARTER : u8 = 4;
+pub const HALF : u8 = 5;
+pub const DOTTED_HALF : u8 = 6;
+
+// pitches
+pub const REST : u8 = 0;
+pub const GS4 : u8 = 9;
+pub const A4 : u8 = 10;
+pub const B4 : u8 = 12;
+pub const C5 : u8 = 13;
+pub const D5 : u8 = 15;
+pub const E5 : u8 = 17;
+pub const F5 : u8 = 18;
+pub const G5 : u8 = 20;
+pub const GS5 : u8 = 21;
+pub const A5 : u8 = 22;
+
+/// Frequency of every pitch, rounded to the nearest Hz (equal temperament, A4 = 440 Hz)
+const PITCH_HZ : [u16; 32] = [
+       0,
+     262,  277,  294,  311,  330,  349,  370,  392,  415,  440,  466,  494, // C4 - B4
+     523,  554,  587,  622,  659,  698,  740,  784,  831,  880,  932,  988, // C5 - B5
+    1047, 1109, 1175, 1245, 1319, 1397, 1480,                               // C6 - F#6
+];
+
+/// Silence at the end of every note
+const GAP_MS : u16 = 15;
+
+/// Tempo on level 1: 150 quarter notes a minute
+const BASE_SIXTEENTH_MS : u16 = 100;
+/// Every level takes this much off a sixteenth note...
+const SIXTEENTH_MS_PER_LEVEL : u16 = 5;
+/// ...until it gets down to this (about twice as fast as level 1)
+const MIN_SIXTEENTH_MS : u16 = 50;
+
+/// Put a note together, see the top of the file
+pub const fn note(pitch: u8, duration: u8) -> u8 {
+    pitch << 3 | duration
+}
+
+/// One note of a song, taken apart
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Note {
+    /// 0 for a rest
+    pub hz: u16,
+    pub sixteenths: u16,
+}
+
+impl Note {
+    pub fn decode(byte: u8) -> Note {
+        Note {
+            hz: PITCH_HZ[(byte >> 3) as usize],
+            sixteenths: DURATIONS[(byte & 0x7) as usize],
+        }
+    }
+}
+
+/// How long a sixteenth note lasts on `level`
+pub fn sixteenth_ms(level: usize) -> u16 {
+    let faster = (level.saturating_sub(1) as u16).saturating_mul(SIXTEENTH_MS_PER_LEVEL);
+    BASE_SIXTEENTH_MS.saturating_sub(faster).max(MIN_SIXTEENTH_MS)
+}
+
+/// Korobeiniki, the Tetris song: the A part twice, then the B part
+pub static KOROBEINIKI : [u8; 98] = [
+    // A
+    note(E5, QUARTER), note(B4, EIGHTH), note(C5, EIGHTH), note(D5, QUARTER), note(C5, EIGHTH), note(B4, EIGHTH),
+    note(A4, QUARTER), note(A4, EIGHTH), note(C5, EIGHTH), note(E5, QUARTER), note(D5, EIGHTH), note(C5, EIGHTH),
+    note(B4, DOTTED_QUARTER), note(C5, EIGHTH), note(D5, QUARTER), note(E5, QUARTER),
+    note(C5, QUARTER), note(A4, QUARTER), note(A4, QUARTER), note(REST, QUARTER),
+    note(REST, EIGHTH), note(D5, QUARTER), note(F5, EIGHTH), note(A5, QUARTER), note(G5, EIGHTH), note(F5, EIGHTH),
+    note(E5, DOTTED_QUARTER), note(C5, EIGHTH), note(E5, QUARTER), note(D5, EIGHTH), note(C5, EIGHTH),
+    note(B4, QUARTER), note(B4, EIGHTH), note(C5, EIGHTH), note(D5, QUARTER), note(E5, QUARTER),
+    note(C5, QUARTER), note(A4, QUARTER), note(A4, QUARTER), note(REST, QUARTER),
+    // A again
+    note(E5, QUARTER), note(B4, EIGHTH), note(C5, EIGHTH), note(D5, QUARTER), note(C5, EIGHTH), note(B4, EIGHTH),
+    note(A4, QUARTER), note(A4, EIGHTH), note(C5, EIGHTH), note(E5, QUARTER), note(D5, EIGHTH), note(C5, EIGHTH),
+    note(B4, DOTTED_QUARTER), note(C5, EIGHTH), note(D5, QUARTER), note(E5, QUARTER),
+    note(C5, QUARTER), note(A4, QUARTER), note(A4, QUARTER), note(REST, QUARTER),
+    note(REST, EIGHTH), note(D5, QUARTER), note(F5, EIGHTH), note(A5, QUARTER), note(G5, EIGHTH), note(F5, EIGHTH),
+    note(E5, DOTTED_QUARTER), note(C5, EIGHTH), note(E5, QUARTER), note(D5, EIGHTH), note(C5, EIGHTH),
+    note(B4, QUARTER), note(B4, EIGHTH), note(C5, EIGHTH), note(D5, QUARTER), note(E5, QUARTER),
+    note(C5, QUARTER), note(A4, QUARTER), note(A4, QUARTER), note(REST, QUARTER),
+    // B
+    note(E5, HALF), note(C5, HALF),
+    note(D5, HALF), note(B4, HALF),
+    note(C5, HALF), note(A4, HALF),
+    note(GS4, HALF), note(B4, QUARTER), note(REST, QUARTER),
+    note(E5, HALF), note(C5, HALF),
+    note(D5, HALF), note(B4, HALF),
+    note(C5, QUARTER), note(E5, QUARTER), note(A5, HALF),
+    note(GS5, DOTTED_HALF), note(REST, QUARTER),
+];
+
+/// Plays a song over and over, a millisecond at a time
+pub struct Player {
+    /// the note that's playing
+    index: usize,
+    /// ms since it started
+    elapsed: u16,
+}
+
+impl Player {
+    pub const fn new() -> Player {
+        Player {
+            index: 0,
+            elapsed: 0,
+        }
+    }
+
+    /// Back to the top of the song
+    pub fn restart(&mut self) {
+        self.index = 0;
+        self.elapsed = 0;
+    }
+
+    /// Move on by a millisecond. Returns the frequency that should be playing for it, 0 for silence.
+    pub fn tick(&mut self, song: &[u8], sixteenth_ms: u16) -> u16 {
+        if self.index >= song.len() {
+            self.restart();
+        }
+        let note = Note::decode(song[self.index]);
+        let length = note.sixteenths * sixteenth_ms;
+        let hz = if self.elapsed + GAP_MS >= length { 0 } else { note.hz };
+
+        self.elapsed += 1;
+        if self.elapsed >= length {
+            self.elapsed = 0;
+            self.index = (self.index + 1) % song.len();
+        }
+        hz
+    }
+}
+
+impl Default for Player {
+    fn default() -> Player {
+        Player::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frequency of `semitones` above A4, worked out instead of looked up
+    fn equal_temperament(semitones: i32) -> f64 {
+        440.0 * 2f64.powf(semitones as f64 / 12.0)
+    }
+
+    #[test]
+    fn pitches_are_in_tune() {
+        // pitch 10 is A4
+        for pitch in 1..32 {
+            let expected = equal_temperament(pitch - 10);
+            let hz = Note::decode(note(pitch as u8, QUARTER)).hz as f64;
+            assert!((hz - expected).abs() <= 0.5, "pitch {}: {} Hz, should be {}", pitch, hz, expected);
+        }
+        assert_eq!(Note::decode(note(REST, QUARTER)).hz, 0);
+    }
+
+    #[test]
+    fn notes_decode() {
+        assert_eq!(Note::decode(note(E5, QUARTER)), Note { hz: 659, sixteenths: 4 });
+        assert_eq!(Note::decode(note(GS4, DOTTED_HALF)), Note { hz: 415, sixteenths: 12 });
+        assert_eq!(Note::decode(note(A5, EIGHTH)), Note { hz: 880, sixteenths: 2 });
+        assert_eq!(Note::decode(note(REST, HALF)), Note { hz: 0, sixteenths: 8 });
+    }
+
+    #[test]
+    fn korobeiniki_is_in_4_4() {
+        // 24 bars, and no note hangs over a bar line
+        let mut sixteenths = 0;
+        for byte in KOROBEINIKI.iter() {
+            let note = Note::decode(*byte);
+            assert!(sixteenths / 16 == (sixteenths + note.sixteenths - 1) / 16,
+                    "note at sixteenth {} crosses a bar line", sixteenths);
+            sixteenths += note.sixteenths;
+        }
+        assert_eq!(sixteenths, 24 * 16);
+    }
+
+    #[test]
+    fn korobeiniki_starts_right() {
+        // E5 B4 C5 D5 C5 B4 A4
+        let hz : [u16; 7] = [659, 494, 523, 587, 523, 494, 440];
+        for (byte, hz) in KOROBEINIKI.iter().zip(hz.iter()) {
+            assert_eq!(Note::decode(*byte).hz, *hz);
+        }
+        // the highest note is A5 and the lowest is G#4
+        let notes = KOROBEINIKI.iter().map(|byte| Note::decode(*byte).hz).filter(|hz| *hz > 0);
+        assert_eq!(notes.clone().max(), Some(880));
+        assert_eq!(notes.min(), Some(415));
+    }
+
+    #[test]
+    fn player_keeps_time() {
+        // 24 bars of 16 sixteenths at level 1
+        const SONG_MS : usize = 24 * 16 * BASE_SIXTEENTH_MS as usize;
+        let mut player = Player::new();
+        let sixteenth_ms = sixteenth_ms(1);
+        let song_ms = SONG_MS;
+        let mut played = [0; SONG_MS];
+        for hz in played.iter_mut() {
+            *hz = player.tick(&KOROBEINIKI, sixteenth_ms);
+        }
+
+        // the first E5 is a quarter note, 400 ms, the last GAP_MS of it silent
+        let quarter = 4 * sixteenth_ms as usize;
+        let gap = GAP_MS as usize;
+        assert!(played[..quarter - gap].iter().all(|hz| *hz == 659));
+        assert!(played[quarter - gap..quarter].iter().all(|hz| *hz == 0));
+        // then an eighth of B4
+        assert_eq!(played[quarter], 494);
+        assert_eq!(played[quarter + 2 * sixteenth_ms as usize - gap - 1], 494);
+
+        // the last bar is a dotted half of G#5 and a rest
+        assert_eq!(played[song_ms - quarter - 1], 0);
+        assert_eq!(played[song_ms - quarter - gap - 1], 831);
+        assert_eq!(played[song_ms - 4 * quarter], 831);
+        assert_eq!(played[song_ms - 4 * quarter - 1], 0);
+
+        // and around it goes
+        assert_eq!(player.tick(&KOROBEINIKI, sixteenth_ms), 659);
+    }
+
+    #[test]
+    fn tempo_goes_up_with_level() {
+        assert_eq!(sixteenth_ms(0), BASE_SIXTEENTH_MS);
+        assert_eq!(sixteenth_ms(1), BASE_SIXTEENTH_MS);
+        for level in 1..30 {
+            assert!(sixteenth_ms(level + 1) <= sixteenth_ms(level));
+            assert!(sixteenth_ms(level) >= MIN_SIXTEENTH_MS);
+        }
+        assert!(sixteenth_ms(2) < sixteenth_ms(1));
+        assert_eq!(sixteenth_ms(1000), MIN_SIXTEENTH_MS);
+    }
+}
@@ -0,0 +1,51 @@
+// Where the system clock comes from.
+//
+// Out of reset the TM4C123 runs off the 16 MHz precision internal oscillator (PIOSC).
+// `initialize` switches over to the 16 MHz crystal on the Launchpad through the PLL:
+//   16 MHz crystal -> PLL (400 MHz) -> divide by 5 -> 80 MHz, see clock::SYSTEM_CLOCK_HZ
+use crate::clock::SYSTEM_CLOCK_HZ;
+
+/// The PLL runs at 400 MHz
+const PLL_HZ : u32 = 400_000_000;
+/// RCC XTAL field value for the 16 MHz crystal on the Launchpad
+const XTAL_16MHZ : u32 = 0x15;
+
+// RCC bits
+const RCC_MOSCDIS : u32 = 1;
+const RCC_XTAL_SHIFT : u32 = 6;
+const RCC_XTAL_MASK : u32 = 0x1F << RCC_XTAL_SHIFT;
+// RCC2 bits
+const RCC2_USERCC2 : u32 = 1 << 31;
+const RCC2_DIV400 : u32 = 1 << 30;
+const RCC2_SYSDIV2_SHIFT : u32 = 22;
+const RCC2_SYSDIV2_MASK : u32 = 0x7F << RCC2_SYSDIV2_SHIFT;
+const RCC2_PWRDN2 : u32 = 1 << 13;
+const RCC2_BYPASS2 : u32 = 1 << 11;
+const RCC2_OSCSRC2_MASK : u32 = 0x7 << 4;
+/// PLL lock raw interrupt status
+const RIS_PLLLRIS : u32 = 1 << 6;
+
+/// Switch the system clock over to the PLL, call this before setting up anything else
+pub fn initialize(p: &tm4c123x::Peripherals) {
+    // 1. use RCC2, it has more SYSDIV bits
+    p.SYSCTL.rcc2.modify(|r, w| unsafe { w.bits( r.bits() | RCC2_USERCC2 ) });
+    // 2. run straight off the oscillator while the PLL is set up
+    p.SYSCTL.rcc2.modify(|r, w| unsafe { w.bits( r.bits() | RCC2_BYPASS2 ) });
+    // 3. turn on the main oscillator, tell the PLL what crystal it has, and pick
+    //    the main oscillator as the clock source (OSCSRC2 = 0)
+    p.SYSCTL.rcc.modify(|r, w| unsafe {
+        w.bits( (r.bits() & !(RCC_MOSCDIS | RCC_XTAL_MASK)) | (XTAL_16MHZ << RCC_XTAL_SHIFT) )
+    });
+    p.SYSCTL.rcc2.modify(|r, w| unsafe { w.bits( r.bits() & !RCC2_OSCSRC2_MASK ) });
+    // 4. power up the PLL
+    p.SYSCTL.rcc2.modify(|r, w| unsafe { w.bits( r.bits() & !RCC2_PWRDN2 ) });
+    // 5. divide 400 MHz directly (DIV400), by SYSDIV2 + 1
+    let sysdiv = PLL_HZ / SYSTEM_CLOCK_HZ - 1;
+    p.SYSCTL.rcc2.modify(|r, w| unsafe {
+        w.bits( (r.bits() & !RCC2_SYSDIV2_MASK) | RCC2_DIV400 | (sysdiv << RCC2_SYSDIV2_SHIFT) )
+    });
+    // 6. wait for the PLL to lock
+    while p.SYSCTL.ris.read().bits() & RIS_PLLLRIS == 0 {}
+    // 7. switch over to the PLL
+    p.SYSCTL.rcc2.modify(|r, w| unsafe { w.bits( r.bits() & !RCC2_BYPASS2 ) });
+}
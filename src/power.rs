@@ -0,0 +1,86 @@
+use cortex_m::peripheral::SCB;
+
+use crate::buttons::{self, Button};
+use crate::clock::SYSTEM_CLOCK_HZ;
+
+// The boards sit on a desk all day, so after a while without any input everything goes to sleep:
+// the LCD stops (see LcdBackend::sleep) and the MCU goes into deep sleep until a button is pressed.
+//
+// In deep sleep the PLL and every peripheral that isn't listed in the DCGC registers stop. Ports
+// D and E keep their clock so the button interrupts can wake us up, and when that happens the
+// hardware brings the clock back to exactly how it was (80 MHz from the PLL).
+
+/// How long without any input before going to sleep (5 minutes)
+const SLEEP_AFTER_SECONDS : u32 = 5 * 60;
+
+/// DSLPCLKCFG: run off the precision internal oscillator in deep sleep, so the crystal can stop
+const DSOSCSRC_PIOSC : u32 = 0x1 << 4;
+/// ports D and E (S1, S2 and the joystick button)
+const BUTTON_PORTS : u32 = 0b1_1000;
+
+/// Keeps track of how long it's been since anybody touched anything
+pub struct SleepTimer {
+    /// ticks left over that don't add up to a whole second yet
+    ticks: u32,
+    /// whole seconds without any input (ticks overflow after less than a minute)
+    seconds: u32,
+}
+
+impl SleepTimer {
+    pub fn new() -> SleepTimer {
+        SleepTimer {
+            ticks: 0,
+            seconds: 0,
+        }
+    }
+
+    /// Call once per frame. `active` is true if the player did anything this frame.
+    /// Returns true once it's time to go to sleep.
+    pub fn update(&mut self, active: bool, elapsed_ticks: u32) -> bool {
+        if active {
+            self.reset();
+            return false;
+        }
+        self.ticks += elapsed_ticks;
+        while self.ticks >= SYSTEM_CLOCK_HZ {
+            self.ticks -= SYSTEM_CLOCK_HZ;
+            self.seconds = self.seconds.saturating_add(1);
+        }
+        self.seconds >= SLEEP_AFTER_SECONDS
+    }
+
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+        self.seconds = 0;
+    }
+}
+
+/// Go into deep sleep until one of the buttons is pressed.
+/// Anything that has interrupts turned on (like Timer0) has to be stopped first, or it'll wake us
+/// up right away.
+pub fn deep_sleep(p: &tm4c123x::Peripherals, scb: &mut SCB) {
+    // 1. keep the button ports running while asleep
+    p.SYSCTL.dcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | BUTTON_PORTS ) });
+    // 2. clock whatever is left off the internal oscillator
+    p.SYSCTL.dslpclkcfg.write(|w| unsafe { w.bits( DSOSCSRC_PIOSC ) });
+    // 3. WFI means deep sleep from now on
+    scb.set_sleepdeep();
+
+    loop {
+        // letting go of a button wakes us up too, so go back to sleep unless something is
+        // actually pressed. With interrupts masked a press can't sneak in between the check
+        // and the WFI, a pending interrupt still wakes the CPU.
+        cortex_m::interrupt::disable();
+        if buttons::is_pressed(p, Button::S1)
+            || buttons::is_pressed(p, Button::S2)
+            || buttons::is_pressed(p, Button::Select) {
+            unsafe { cortex_m::interrupt::enable() };
+            break;
+        }
+        cortex_m::asm::wfi();
+        unsafe { cortex_m::interrupt::enable() };
+    }
+
+    // 4. back to regular sleep, for Timer0::wait_for_next_frame
+    scb.clear_sleepdeep();
+}
@@ -0,0 +1,192 @@
+use cortex_m::peripheral::{DCB, DWT};
+
+use crate::clock::SYSTEM_CLOCK_HZ;
+use crate::uart::Uart0;
+
+// Times the phases of each frame with the DWT cycle counter, which counts every core clock
+// cycle. Timer0 runs off the same clock, so a frame is `budget` cycles long.
+//
+// Every frame goes:  [wait for timer] Idle -> Input -> Logic -> Draw -> [back to Idle]
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// waiting for the next frame
+    Idle = 0,
+    /// polling the input source and harvesting noise
+    Input = 1,
+    /// game.run_loop
+    Logic = 2,
+    /// game.draw, which is mostly waiting on SPI
+    Draw = 3,
+}
+
+const PHASES : usize = 4;
+const PHASE_NAMES : [&[u8]; PHASES] = [b"idle", b"input", b"logic", b"draw"];
+
+/// to turn cycles into microseconds
+const CYCLES_PER_US : u32 = SYSTEM_CLOCK_HZ / 1_000_000;
+
+#[derive(Clone, Copy)]
+struct Stats {
+    min: u32,
+    max: u32,
+    total: u64,
+    count: u32,
+}
+
+impl Stats {
+    const fn new() -> Stats {
+        Stats {
+            min: u32::MAX,
+            max: 0,
+            total: 0,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, cycles: u32) {
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        self.total += cycles as u64;
+        self.count += 1;
+    }
+
+    fn average(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total / self.count as u64) as u32
+        }
+    }
+}
+
+pub struct Profiler {
+    /// cycles available in one frame
+    budget: u32,
+    phases: [Stats; PHASES],
+    /// time spent in Input + Logic + Draw
+    busy: Stats,
+    /// frames where the busy time went over the budget
+    overruns: u32,
+    /// frame starts that were missed entirely because a frame ran long
+    missed: u32,
+    current: Phase,
+    /// cycle count when the current phase started
+    phase_start: u32,
+    /// cycle count when the current frame's work started
+    frame_start: u32,
+}
+
+impl Profiler {
+    /// `budget` is the length of a frame in clock cycles
+    pub fn new(dcb: &mut DCB, dwt: &mut DWT, budget: u32) -> Profiler {
+        // the cycle counter is part of the trace hardware, which is off by default
+        dcb.enable_trace();
+        dwt.enable_cycle_counter();
+
+        let now = cycles();
+        Profiler {
+            budget,
+            phases: [Stats::new(); PHASES],
+            busy: Stats::new(),
+            overruns: 0,
+            missed: 0,
+            current: Phase::Idle,
+            phase_start: now,
+            frame_start: now,
+        }
+    }
+
+    /// Finish the current phase and start timing `phase`
+    pub fn enter(&mut self, phase: Phase) {
+        let now = cycles();
+        // wrapping_sub takes care of the counter rolling over (every ~54 seconds at 80 MHz)
+        self.phases[self.current as usize].add(now.wrapping_sub(self.phase_start));
+
+        if phase == Phase::Idle && self.current != Phase::Idle {
+            // the frame's work is done
+            let busy = now.wrapping_sub(self.frame_start);
+            self.busy.add(busy);
+            if busy > self.budget {
+                self.overruns += 1;
+            }
+        } else if phase != Phase::Idle && self.current == Phase::Idle {
+            self.frame_start = now;
+        }
+
+        self.current = phase;
+        self.phase_start = now;
+    }
+
+    /// For when the frame rate changes, stats from the old rate get thrown out
+    pub fn set_budget(&mut self, budget: u32) {
+        self.budget = budget;
+        self.reset();
+    }
+
+    /// Number of frames timed since the last reset
+    pub fn frames(&self) -> u32 {
+        self.busy.count
+    }
+
+    /// Worst frame since the last reset, as a percentage of the budget
+    pub fn worst_load(&self) -> u32 {
+        (self.busy.max as u64 * 100 / self.budget as u64) as u32
+    }
+
+    /// Start collecting from scratch, the phase being timed keeps going
+    pub fn reset(&mut self) {
+        self.phases = [Stats::new(); PHASES];
+        self.busy = Stats::new();
+        self.overruns = 0;
+        self.missed = 0;
+    }
+
+    /// Count frames that went by without being run, see Timer0::wait_for_next_frame
+    pub fn add_missed(&mut self, frames: u32) {
+        self.missed = self.missed.saturating_add(frames);
+    }
+
+    /// Dump everything over UART in microseconds, one line per phase:
+    ///   profile: draw min 1200 avg 1350 max 9800 us
+    ///   profile: frame min 1500 avg 1700 max 10200 us budget 14285 us overruns 0/70 missed 0
+    ///   profile: buttons max 14100 us
+    /// `button_latency` is the longest a button press waited to be handled, in clock cycles
+    /// (see buttons::take_worst_latency).
+    pub fn report(&self, uart: &Uart0, button_latency: u32) {
+        for (name, stats) in PHASE_NAMES.iter().zip(self.phases.iter()) {
+            uart.write_bytes(b"profile: ");
+            uart.write_bytes(name);
+            write_stats(uart, stats);
+            uart.write_bytes(b" us\r\n");
+        }
+        uart.write_bytes(b"profile: frame");
+        write_stats(uart, &self.busy);
+        uart.write_bytes(b" us budget ");
+        uart.write_decimal(self.budget / CYCLES_PER_US);
+        uart.write_bytes(b" us overruns ");
+        uart.write_decimal(self.overruns);
+        uart.write_byte(b'/');
+        uart.write_decimal(self.busy.count);
+        uart.write_bytes(b" missed ");
+        uart.write_decimal(self.missed);
+        uart.write_bytes(b"\r\n");
+        uart.write_bytes(b"profile: buttons max ");
+        uart.write_decimal(button_latency / CYCLES_PER_US);
+        uart.write_bytes(b" us\r\n");
+    }
+}
+
+fn write_stats(uart: &Uart0, stats: &Stats) {
+    let min = if stats.count == 0 { 0 } else { stats.min };
+    uart.write_bytes(b" min ");
+    uart.write_decimal(min / CYCLES_PER_US);
+    uart.write_bytes(b" avg ");
+    uart.write_decimal(stats.average() / CYCLES_PER_US);
+    uart.write_bytes(b" max ");
+    uart.write_decimal(stats.max / CYCLES_PER_US);
+}
+
+fn cycles() -> u32 {
+    unsafe { (*DWT::ptr()).cyccnt.read() }
+}
@@ -0,0 +1,214 @@
+use fourtris::rng::Rng;
+
+// Decides which piece comes next. Sits between the game and a source of uniform 0-6 indices
+// (Randy), and is itself an Rng so the game doesn't know the difference.
+//
+// Piece indices follow the order of TetriminoType: I, O, J, L, S, Z, T
+
+const PIECES : usize = 7;
+const S_PIECE : usize = 4;
+const Z_PIECE : usize = 5;
+const HISTORY_SIZE : usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// every piece is equally likely every time
+    Uniform,
+    /// deal all 7 pieces in a random order, then start a new bag
+    Bag,
+    /// TGM style: reroll up to `rerolls` times if the piece is one of the last 4 dealt
+    History { rerolls: u8 },
+}
+
+pub struct Randomizer<R: Rng> {
+    source: R,
+    strategy: Strategy,
+    bag: [usize; PIECES],
+    /// pieces left in the bag, dealt from the end
+    bag_remaining: usize,
+    /// most recent piece first
+    history: [usize; HISTORY_SIZE],
+}
+
+impl<R: Rng> Randomizer<R> {
+    pub fn new(source: R, strategy: Strategy) -> Randomizer<R> {
+        let mut randomizer = Randomizer {
+            source,
+            strategy,
+            bag: [0; PIECES],
+            bag_remaining: 0,
+            history: [0; HISTORY_SIZE],
+        };
+        randomizer.reset();
+        randomizer
+    }
+
+    /// Switch strategies, starting over like it's a new game
+    pub fn set_strategy(&mut self, strategy: Strategy) {
+        self.strategy = strategy;
+        self.reset();
+    }
+
+    pub fn source_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+
+    /// Forget the bag and history, call this when a new game starts
+    pub fn reset(&mut self) {
+        self.bag_remaining = 0;
+        // TGM starts with a history full of S and Z, so the first piece is unlikely to be one of them
+        self.history = [Z_PIECE, S_PIECE, Z_PIECE, S_PIECE];
+    }
+
+    /// A uniform number in 0..n, n has to be 7 or less
+    fn below(&mut self, n: usize) -> usize {
+        loop {
+            let candidate = self.source.next();
+            if candidate < n {
+                return candidate;
+            }
+        }
+    }
+
+    fn next_from_bag(&mut self) -> usize {
+        if self.bag_remaining == 0 {
+            // Fisher-Yates shuffle a fresh bag
+            for (i, piece) in self.bag.iter_mut().enumerate() {
+                *piece = i;
+            }
+            for i in (1..PIECES).rev() {
+                let j = self.below(i + 1);
+                self.bag.swap(i, j);
+            }
+            self.bag_remaining = PIECES;
+        }
+        self.bag_remaining -= 1;
+        self.bag[self.bag_remaining]
+    }
+
+    fn next_from_history(&mut self, rerolls: u8) -> usize {
+        let mut piece = self.below(PIECES);
+        for _ in 0..rerolls {
+            if !self.history.contains(&piece) {
+                break;
+            }
+            piece = self.below(PIECES);
+        }
+
+        // shift the history along, dropping the oldest piece
+        for i in (1..HISTORY_SIZE).rev() {
+            self.history[i] = self.history[i - 1];
+        }
+        self.history[0] = piece;
+        piece
+    }
+}
+
+impl<R: Rng> Rng for Randomizer<R> {
+    fn next(&mut self) -> usize {
+        match self.strategy {
+            Strategy::Uniform => self.below(PIECES),
+            Strategy::Bag => self.next_from_bag(),
+            Strategy::History { rerolls } => self.next_from_history(rerolls),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out `numbers` over and over
+    struct Script {
+        numbers: Vec<usize>,
+        position: usize,
+    }
+
+    impl Script {
+        fn new(numbers: &[usize]) -> Script {
+            Script { numbers: numbers.to_vec(), position: 0 }
+        }
+
+        /// A long jumble of 0 - 8, so some get thrown away by `below`
+        fn jumble() -> Script {
+            let mut x : u32 = 1;
+            let numbers : Vec<usize> =
+                (0..997).map(|_| {
+                            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                            (x >> 16) as usize % 9
+                        })
+                        .collect();
+            Script::new(&numbers)
+        }
+    }
+
+    impl Rng for Script {
+        fn next(&mut self) -> usize {
+            let number = self.numbers[self.position % self.numbers.len()];
+            self.position += 1;
+            number
+        }
+    }
+
+    fn deal(randomizer: &mut Randomizer<Script>, count: usize) -> Vec<usize> {
+        (0..count).map(|_| randomizer.next()).collect()
+    }
+
+    fn is_a_whole_bag(pieces: &[usize]) -> bool {
+        let mut sorted = pieces.to_vec();
+        sorted.sort_unstable();
+        sorted == (0..PIECES).collect::<Vec<usize>>()
+    }
+
+    #[test]
+    fn every_bag_has_every_piece() {
+        let mut randomizer = Randomizer::new(Script::jumble(), Strategy::Bag);
+        let pieces = deal(&mut randomizer, PIECES * 50);
+        for bag in pieces.chunks(PIECES) {
+            assert!(is_a_whole_bag(bag), "{:?}", bag);
+        }
+        // and they don't all come out in the same order
+        assert!(pieces.chunks(PIECES).any(|bag| bag != &pieces[..PIECES]));
+    }
+
+    #[test]
+    fn history_rerolls_recent_pieces() {
+        // starts out with S and Z in the history: S, Z, S are rerolled, then the I sticks
+        let mut randomizer = Randomizer::new(Script::new(&[S_PIECE, Z_PIECE, S_PIECE, 0]), Strategy::History { rerolls: 3 });
+        assert_eq!(randomizer.next(), 0);
+        // only 2 rerolls, so it gives up and the last S goes through
+        let mut randomizer = Randomizer::new(Script::new(&[S_PIECE, Z_PIECE, S_PIECE, 0]), Strategy::History { rerolls: 2 });
+        assert_eq!(randomizer.next(), S_PIECE);
+        // no rerolls at all is the same as uniform
+        let mut randomizer = Randomizer::new(Script::new(&[Z_PIECE]), Strategy::History { rerolls: 0 });
+        assert_eq!(deal(&mut randomizer, 3), [Z_PIECE; 3]);
+
+        // with plenty of rerolls nothing comes back while it's one of the last 4
+        let mut randomizer = Randomizer::new(Script::jumble(), Strategy::History { rerolls: 50 });
+        let mut pieces = vec![S_PIECE, Z_PIECE, S_PIECE, Z_PIECE];
+        pieces.extend(deal(&mut randomizer, 500));
+        for recent in pieces.windows(HISTORY_SIZE + 1) {
+            assert!(!recent[..HISTORY_SIZE].contains(&recent[HISTORY_SIZE]), "{:?}", recent);
+        }
+    }
+
+    #[test]
+    fn reset_starts_a_new_bag() {
+        let mut randomizer = Randomizer::new(Script::jumble(), Strategy::Bag);
+        deal(&mut randomizer, 3);
+        randomizer.reset();
+        assert!(is_a_whole_bag(&deal(&mut randomizer, PIECES)));
+
+        deal(&mut randomizer, 5);
+        randomizer.set_strategy(Strategy::Bag);
+        assert!(is_a_whole_bag(&deal(&mut randomizer, PIECES)));
+
+        // and a fresh history, so S and Z are rerolled again
+        let mut randomizer = Randomizer::new(Script::new(&[0, 1, 2, 3, S_PIECE, 6]), Strategy::History { rerolls: 1 });
+        assert_eq!(deal(&mut randomizer, 4), [0, 1, 2, 3]);
+        // S left the history a while ago
+        assert_eq!(randomizer.next(), S_PIECE);
+        randomizer.reset();
+        assert_eq!(randomizer.next(), 6);
+    }
+}
@@ -1,77 +1,336 @@
 use fourtris::rng::Rng;
 
+/// Default buffer size, plenty for the pieces
 pub const BUF_SIZE : usize = 18;
+/// Pieces are picked with numbers in 0..7
+pub const PIECES : usize = 7;
 
-pub struct Randy {
-    buf: [usize; BUF_SIZE],
+/// Starting state for the PRNG, it gets stirred up by harvested bits before it's needed
+/// (any non-zero state works for xoshiro)
+const INITIAL_STATE : [u32; 4] = [0x9E37_79B9, 0x243F_6A88, 0xB7E1_5162, 0x1234_5678];
+
+/// Turns harvested bits into uniform numbers in 0..bound, holding on to up to N of them
+/// for when they're needed. Once they run out, a PRNG takes over.
+pub struct Randy<const N: usize = BUF_SIZE> {
+    buf: [usize; N],
     head: usize,
     tail: usize,
+    /// numbers are in 0..bound
+    bound: usize,
+    /// bits needed to cover 0..bound, candidates that come out >= bound are thrown out
+    bits_per_number: u32,
     candidate: usize,
-    bits_accumulated: usize,
+    bits_accumulated: u32,
     /// Number of available random numbers
     nums_available: usize,
+    /// xoshiro128** state, used when we run out of harvested numbers
+    state: [u32; 4],
+    /// harvested bits waiting to be mixed into the PRNG state
+    pool: u32,
+    pool_bits: usize,
+    /// which word of the state the pool gets mixed into next
+    reseed_index: usize,
+    /// when set, every number comes from the PRNG and harvested bits are ignored
+    seeded: bool,
 }
 
 impl Randy {
+    /// Numbers for picking pieces (0..7)
     pub fn new() -> Randy {
+        Randy::with_bound(PIECES)
+    }
+}
+
+impl Default for Randy {
+    fn default() -> Randy {
+        Randy::new()
+    }
+}
+
+impl<const N: usize> Randy<N> {
+    /// Numbers in 0..bound, `bound` has to be at least 1
+    pub fn with_bound(bound: usize) -> Randy<N> {
+        // the PRNG only hands out 32 bits at a time
+        assert!(bound > 0 && (bound - 1) as u64 <= u32::MAX as u64 && N > 0);
         Randy {
-            buf: Default::default(),
+            buf: [0; N],
             head: 0,
             tail: 0,
+            bound,
+            // ceil(log2(bound)), a bound of 1 doesn't need any bits at all
+            bits_per_number: usize::BITS - (bound - 1).leading_zeros(),
             candidate: 0,
             bits_accumulated: 0,
             nums_available: 0,
+            state: INITIAL_STATE,
+            pool: 0,
+            pool_bits: 0,
+            reseed_index: 0,
+            seeded: false,
         }
     }
 
+    /// Deterministic mode: throw out the harvested numbers and restart the PRNG from `seed`.
+    /// Until `unseed` is called, the numbers only depend on the seed.
+    pub fn seed(&mut self, seed: u32) {
+        self.seeded = true;
+        self.head = 0;
+        self.tail = 0;
+        self.candidate = 0;
+        self.bits_accumulated = 0;
+        self.nums_available = 0;
+
+        // splitmix32 to fill out the state, so nearby seeds give very different sequences
+        let mut x = seed;
+        for word in self.state.iter_mut() {
+            x = x.wrapping_add(0x9E37_79B9);
+            let mut z = x;
+            z = (z ^ (z >> 16)).wrapping_mul(0x85EB_CA6B);
+            z = (z ^ (z >> 13)).wrapping_mul(0xC2B2_AE35);
+            *word = z ^ (z >> 16);
+        }
+        if self.state == [0; 4] {
+            self.state = INITIAL_STATE;
+        }
+    }
+
+    /// Go back to using harvested bits
+    pub fn unseed(&mut self) {
+        self.seeded = false;
+    }
+
     pub fn add_bit(&mut self, bit: usize) {
-        if self.nums_available == BUF_SIZE {
+        // a seeded game has to play out the same no matter what the noise is doing
+        if self.seeded {
+            return;
+        }
+
+        // every bit goes into the reseed pool, even when the buffer is full
+        self.pool = (self.pool << 1) | (bit as u32 & 1);
+        self.pool_bits += 1;
+        if self.pool_bits == 32 {
+            self.reseed();
+        }
+
+        if self.nums_available == N {
             return;
         }
 
         self.candidate <<= 1;
-        self.candidate |= bit;
+        self.candidate |= bit & 1;
         self.bits_accumulated += 1;
-        if self.bits_accumulated == 3 {
+        if self.bits_accumulated >= self.bits_per_number {
             self.bits_accumulated = 0;
-            // we have 7 pieces, so the indices for the Knuth shuffle
-            // will be 0-6
-            if self.candidate < 7 {
+            // anything past the bound gets thrown out, otherwise the low numbers
+            // would show up more often
+            if self.candidate < self.bound {
                 self.buf[self.tail] = self.candidate;
                 self.tail += 1;
-                self.tail %= BUF_SIZE;
-                // prepare for a new number
-                self.candidate = 0;
+                self.tail %= N;
                 self.nums_available += 1;
-            } else {
-                // throw out the number
-                self.candidate = 0;
             }
+            // prepare for a new number
+            self.candidate = 0;
         }
     }
 
+    pub fn bound(&self) -> usize {
+        self.bound
+    }
+
     pub fn capacity(&self) -> usize {
-        BUF_SIZE
+        N
     }
     pub fn nums_available(&self) -> usize {
         self.nums_available
     }
+
+    /// Mix the pool into one word of the PRNG state
+    fn reseed(&mut self) {
+        // spread the pool bits around a bit (murmur3 finalizer) so a mostly zero pool
+        // doesn't leave the state mostly unchanged
+        let mut mixed = self.pool;
+        mixed ^= mixed >> 16;
+        mixed = mixed.wrapping_mul(0x85EB_CA6B);
+        mixed ^= mixed >> 13;
+        mixed = mixed.wrapping_mul(0xC2B2_AE35);
+        mixed ^= mixed >> 16;
+
+        self.state[self.reseed_index] ^= mixed;
+        self.reseed_index = (self.reseed_index + 1) % self.state.len();
+        // xoshiro gets stuck on an all zero state
+        if self.state == [0; 4] {
+            self.state = INITIAL_STATE;
+        }
+
+        self.pool = 0;
+        self.pool_bits = 0;
+    }
+
+    /// xoshiro128**
+    pub fn next_u32(&mut self) -> u32 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 9;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(11);
+
+        result
+    }
 }
 
-// NOTE: perhaps it would be better to use a PRNG seeded from the
-//       ADC or temperature sensor readings, idk
-impl Rng for Randy {
+impl<const N: usize> Rng for Randy<N> {
+    /// A uniform number in 0..bound
     fn next(&mut self) -> usize {
         if self.nums_available > 0 {
             let ret = self.buf[self.head];
             self.head += 1;
-            self.head %= BUF_SIZE;
+            self.head %= N;
             self.nums_available -= 1;
             ret
+        } else if self.bits_per_number == 0 {
+            0
         } else {
-            // TODO: how to handle this?
-            //       Eh, 3 is pretty random, who would have guessed I would do this? muahahah
-            3
+            // out of harvested numbers, fall back on the PRNG
+            // same deal as add_bit: take just enough bits (the top ones are the best)
+            // and throw out anything past the bound
+            loop {
+                let candidate = (self.next_u32() >> (32 - self.bits_per_number)) as usize;
+                if candidate < self.bound {
+                    return candidate;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift32, stands in for the noise we'd normally harvest
+    struct Noise(u32);
+
+    impl Noise {
+        fn bit(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            (self.0 >> 31) as usize
+        }
+    }
+
+    const DRAWS : usize = 70_000;
+
+    /// Pearson's chi-square statistic for counts that should all be DRAWS / counts.len()
+    fn chi_square(counts: &[usize]) -> f64 {
+        let expected = DRAWS as f64 / counts.len() as f64;
+        counts.iter()
+            .map(|&count| (count as f64 - expected) * (count as f64 - expected) / expected)
+            .sum()
+    }
+
+    /// Critical values at p = 0.001 for a few degrees of freedom, a uniform source fails
+    /// one of these about once in a thousand runs (and the inputs here are fixed, so never)
+    fn critical_value(degrees_of_freedom: usize) -> f64 {
+        match degrees_of_freedom {
+            0 => 0.0,
+            1 => 10.83,
+            2 => 13.82,
+            6 => 22.46,
+            9 => 27.88,
+            12 => 32.91,
+            15 => 37.70,
+            _ => panic!("no critical value for {} degrees of freedom", degrees_of_freedom),
+        }
+    }
+
+    /// Draw only harvested numbers, adding bits whenever the buffer runs dry
+    fn harvested_counts<const N: usize>(bound: usize, counts: &mut [usize]) {
+        let mut randy : Randy<N> = Randy::with_bound(bound);
+        let mut noise = Noise(0x1234_5678);
+        for _ in 0..DRAWS {
+            while randy.nums_available() == 0 {
+                randy.add_bit(noise.bit());
+            }
+            counts[randy.next()] += 1;
+        }
+    }
+
+    /// Draw only from the PRNG
+    fn prng_counts(bound: usize, seed: u32, counts: &mut [usize]) {
+        let mut randy : Randy = Randy::with_bound(bound);
+        randy.seed(seed);
+        for _ in 0..DRAWS {
+            counts[randy.next()] += 1;
+        }
+    }
+
+    #[test]
+    fn harvested_numbers_are_uniform() {
+        let mut counts = [0; 7];
+        harvested_counts::<BUF_SIZE>(7, &mut counts);
+        assert!(chi_square(&counts) < critical_value(6), "{:?}", counts);
+
+        let mut counts = [0; 10];
+        harvested_counts::<4>(10, &mut counts);
+        assert!(chi_square(&counts) < critical_value(9), "{:?}", counts);
+
+        let mut counts = [0; 13];
+        harvested_counts::<32>(13, &mut counts);
+        assert!(chi_square(&counts) < critical_value(12), "{:?}", counts);
+
+        let mut counts = [0; 16];
+        harvested_counts::<1>(16, &mut counts);
+        assert!(chi_square(&counts) < critical_value(15), "{:?}", counts);
+    }
+
+    #[test]
+    fn prng_numbers_are_uniform() {
+        for &bound in &[2, 3, 7, 10, 13, 16] {
+            let mut counts = [0; 16];
+            prng_counts(bound, 12345, &mut counts[..bound]);
+            assert!(chi_square(&counts[..bound]) < critical_value(bound - 1), "{:?}", &counts[..bound]);
+        }
+    }
+
+    #[test]
+    fn bound_of_one_is_always_zero() {
+        let mut randy : Randy = Randy::with_bound(1);
+        let mut noise = Noise(99);
+        for _ in 0..100 {
+            randy.add_bit(noise.bit());
+            assert_eq!(randy.next(), 0);
+        }
+    }
+
+    #[test]
+    fn buffer_holds_n_numbers() {
+        let mut randy : Randy<5> = Randy::with_bound(4);
+        let mut noise = Noise(7);
+        for _ in 0..1000 {
+            randy.add_bit(noise.bit());
+        }
+        assert_eq!(randy.nums_available(), 5);
+        assert_eq!(randy.capacity(), 5);
+    }
+
+    #[test]
+    fn seeded_numbers_ignore_noise() {
+        let mut a = Randy::new();
+        let mut b = Randy::new();
+        a.seed(2024);
+        b.seed(2024);
+        let mut noise = Noise(1);
+        for _ in 0..1000 {
+            b.add_bit(noise.bit());
+            assert_eq!(a.next(), b.next());
         }
     }
 }
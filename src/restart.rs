@@ -0,0 +1,66 @@
+/// What happened to the restart hold during the last frame
+pub enum HoldStatus {
+    /// button isn't being held (or we're waiting for it to be released after a restart)
+    Idle,
+    /// button is being held, but not long enough yet
+    Holding,
+    /// button was released before the hold time elapsed
+    Cancelled,
+    /// button has been held long enough, restart the game!
+    Restart,
+}
+
+/// Keeps track of how long the joystick select button has been held so that
+/// bumping the joystick doesn't wipe out a game.
+pub struct RestartHold {
+    /// number of timer ticks the button needs to be held for
+    required_ticks: u32,
+    /// number of timer ticks the button has been held for so far
+    held_ticks: u32,
+    /// set once a restart has been triggered, only cleared when the button is released
+    /// so we get exactly one restart per hold
+    fired: bool,
+}
+
+impl RestartHold {
+    pub fn new(required_ticks: u32) -> RestartHold {
+        RestartHold {
+            required_ticks,
+            held_ticks: 0,
+            fired: false,
+        }
+    }
+
+    /// Call once per frame with the state of the button and the number of timer ticks
+    /// that elapsed since the last call.
+    pub fn update(&mut self, pressed: bool, elapsed_ticks: u32) -> HoldStatus {
+        if !pressed {
+            let cancelled = self.held_ticks > 0 && !self.fired;
+            self.held_ticks = 0;
+            self.fired = false;
+            return if cancelled { HoldStatus::Cancelled } else { HoldStatus::Idle };
+        }
+
+        if self.fired {
+            // let go already!
+            return HoldStatus::Idle;
+        }
+
+        self.held_ticks = self.held_ticks.saturating_add(elapsed_ticks);
+        if self.held_ticks >= self.required_ticks {
+            self.fired = true;
+            HoldStatus::Restart
+        } else {
+            HoldStatus::Holding
+        }
+    }
+
+    /// How much of a progress bar that is `width` pixels wide should be filled in
+    pub fn progress(&self, width: u8) -> u8 {
+        if self.fired {
+            return width;
+        }
+        // u64 so we don't overflow with large tick counts
+        ((self.held_ticks as u64 * width as u64) / self.required_ticks as u64) as u8
+    }
+}
@@ -0,0 +1,143 @@
+use fourtris::game::Input;
+
+// Seed mode: before every game the player picks a seed, and the pieces come from a PRNG
+// started from that seed instead of from noise. Two boards using the same seed (and the same
+// randomizer) deal the exact same pieces, so people can race each other.
+
+/// Seeds are shown as 5 decimal digits
+pub const SEED_DIGITS : usize = 5;
+pub const MAX_SEED : u32 = 99_999;
+
+/// The seed entry screen, edits one digit at a time
+///  - joystick left/right: pick a digit
+///  - S1/S2: make the digit bigger/smaller
+///  - joystick down: roll a random seed
+pub struct SeedPicker {
+    seed: u32,
+    /// which digit is being edited, 0 is the leftmost one
+    cursor: usize,
+}
+
+/// What the picker wants after handling a frame of input
+#[derive(Clone, Copy, PartialEq)]
+pub enum PickerStatus {
+    Unchanged,
+    /// the seed or cursor changed and needs to be redrawn
+    Changed,
+    /// the player asked for a random seed
+    Roll,
+}
+
+impl SeedPicker {
+    pub fn new(seed: u32) -> SeedPicker {
+        SeedPicker {
+            seed: seed % (MAX_SEED + 1),
+            cursor: SEED_DIGITS - 1,
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed % (MAX_SEED + 1);
+    }
+
+    /// `pressed` should only have what was pressed this frame (see input::Presses),
+    /// so holding something down only counts once
+    pub fn update(&mut self, pressed: &Input) -> PickerStatus {
+        if pressed.down {
+            return PickerStatus::Roll;
+        }
+
+        let place = 10u32.pow((SEED_DIGITS - 1 - self.cursor) as u32);
+        let digit = self.seed / place % 10;
+        if pressed.left && self.cursor > 0 {
+            self.cursor -= 1;
+        } else if pressed.right && self.cursor < SEED_DIGITS - 1 {
+            self.cursor += 1;
+        } else if pressed.cw_rotate {
+            // wrap around within the digit, 9 -> 0
+            self.seed = self.seed - digit * place + (digit + 1) % 10 * place;
+        } else if pressed.ccw_rotate {
+            self.seed = self.seed - digit * place + (digit + 9) % 10 * place;
+        } else {
+            return PickerStatus::Unchanged;
+        }
+        PickerStatus::Changed
+    }
+}
+
+/// The seed as ASCII digits, with leading zeros
+pub fn seed_text(seed: u32) -> [u8; SEED_DIGITS] {
+    let mut text = [b'0'; SEED_DIGITS];
+    let mut seed = seed;
+    for c in text.iter_mut().rev() {
+        *c = b'0' + (seed % 10) as u8;
+        seed /= 10;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Replay;
+    use crate::randomizer::{Randomizer, Strategy};
+    use crate::randy::Randy;
+    use crate::testing::{self, Draw};
+
+    const FRAMES : usize = 2000;
+
+    /// Start a game the way main does after the seed is picked, then replay `script` into it
+    fn play(seed: u32, strategy: Strategy, script: &[u8]) -> Vec<Draw> {
+        let mut rng = Randomizer::new(Randy::new(), strategy);
+        // noise from before the seed was picked shouldn't matter
+        rng.source_mut().add_bit(1);
+        rng.source_mut().seed(seed);
+        rng.reset();
+        testing::play_game(&mut Replay::new(script), &mut rng, FRAMES)
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let script = testing::busy_script(FRAMES);
+        for strategy in [Strategy::Uniform, Strategy::Bag, Strategy::History { rerolls: 4 }] {
+            let first = play(12345, strategy, &script);
+            assert!(first.len() > 1000);
+            assert_eq!(first, play(12345, strategy, &script));
+            assert_ne!(first, play(12346, strategy, &script));
+        }
+    }
+
+    #[test]
+    fn picking_a_seed() {
+        let mut picker = SeedPicker::new(123_456);
+        assert_eq!(picker.seed(), 23_456);
+        assert_eq!(seed_text(picker.seed()), *b"23456");
+
+        // the cursor starts on the last digit, and 6 + 5 wraps around to 1
+        for _ in 0..5 {
+            assert!(picker.update(&Input { cw_rotate: true, ..Default::default() }) == PickerStatus::Changed);
+        }
+        assert_eq!(picker.seed(), 23_451);
+        // the first digit, and 2 - 3 wraps around to 9
+        for _ in 0..10 {
+            picker.update(&Input { left: true, ..Default::default() });
+        }
+        assert_eq!(picker.cursor(), 0);
+        for _ in 0..3 {
+            picker.update(&Input { ccw_rotate: true, ..Default::default() });
+        }
+        assert_eq!(seed_text(picker.seed()), *b"93451");
+
+        assert!(picker.update(&Default::default()) == PickerStatus::Unchanged);
+        assert!(picker.update(&Input { down: true, ..Default::default() }) == PickerStatus::Roll);
+        assert_eq!(seed_text(7), *b"00007");
+    }
+}
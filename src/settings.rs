@@ -0,0 +1,519 @@
+use crate::crc;
+use crate::eeprom::{self, Storage};
+use crate::randomizer::Strategy;
+
+// Settings are kept in the EEPROM (see `load` and `save`). In there they take up RECORD_WORDS words:
+//   word 0          TAG in the top half, VERSION in the bottom half
+//   word 1          how many field bytes follow
+//   words 2 - 4     one byte per field, in the order of the Field constants, packed lowest byte first
+//   word 5          CRC of words 0 - 4
+//
+// New fields only ever go on the end, so a record saved before a field existed is just shorter, and
+// the missing fields get their defaults. The CRC comes right after the last field, so where it is
+// depends on word 1 (see record_words): 7 fields is 5 words with the CRC in word 4.
+// VERSION only goes up when an existing field changes meaning, `migrate` has to convert records
+// from older versions when that happens.
+
+/// How the pieces get moved around
+#[derive(Clone, Copy, PartialEq)]
+pub enum ControlScheme {
+    /// joystick moves, S1/S2 rotate
+    Joystick,
+    /// tilt the board to move, flick it to rotate
+    Tilt,
+}
+
+/// How far the board has to be tilted before a piece moves
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sensitivity {
+    Low,
+    Medium,
+    High,
+}
+
+/// Colors of the pieces, see lcd_backend.rs
+#[derive(Clone, Copy, PartialEq)]
+pub enum Palette {
+    /// the colors this has always had
+    Classic,
+    /// the colors from the Tetris guideline (cyan I, yellow O, purple T...)
+    Guideline,
+}
+
+/// How often the screen gets updated, see speed.rs for how fast the game runs
+#[derive(Clone, Copy, PartialEq)]
+pub enum FrameRate {
+    Fps50,
+    Fps60,
+    Fps70,
+}
+
+impl FrameRate {
+    pub fn hz(self) -> u32 {
+        match self {
+            FrameRate::Fps50 => 50,
+            FrameRate::Fps60 => 60,
+            FrameRate::Fps70 => 70,
+        }
+    }
+
+    /// The next preset up, wraps around to the slowest
+    pub fn faster(self) -> FrameRate {
+        match self {
+            FrameRate::Fps50 => FrameRate::Fps60,
+            FrameRate::Fps60 => FrameRate::Fps70,
+            FrameRate::Fps70 => FrameRate::Fps50,
+        }
+    }
+
+    /// The next preset down, wraps around to the fastest
+    pub fn slower(self) -> FrameRate {
+        match self {
+            FrameRate::Fps50 => FrameRate::Fps70,
+            FrameRate::Fps60 => FrameRate::Fps50,
+            FrameRate::Fps70 => FrameRate::Fps60,
+        }
+    }
+}
+
+/// Everything the player can change without rebuilding the firmware
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub control_scheme: ControlScheme,
+    pub tilt_sensitivity: Sensitivity,
+    /// how the next piece gets picked
+    pub randomizer: Strategy,
+    /// pick a seed before every game so the pieces can be replayed
+    pub seed_mode: bool,
+    /// time every frame, and report over UART and on the screen
+    pub profile: bool,
+    pub frame_rate: FrameRate,
+    /// LCD backlight, in percent
+    pub brightness: u8,
+    /// how far the joystick has to be pushed, high means not very far
+    pub joystick_sensitivity: Sensitivity,
+    /// S1 rotates counterclockwise and S2 clockwise, instead of the other way around
+    pub swap_rotation: bool,
+    pub palette: Palette,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            control_scheme: ControlScheme::Joystick,
+            tilt_sensitivity: Sensitivity::Medium,
+            randomizer: Strategy::Uniform,
+            seed_mode: false,
+            profile: false,
+            // turns out 70 fps is more fun! :)
+            frame_rate: FrameRate::Fps70,
+            brightness: 100,
+            joystick_sensitivity: Sensitivity::Medium,
+            swap_rotation: false,
+            palette: Palette::Classic,
+        }
+    }
+}
+
+impl Settings {
+    /// Step down through the brightness presets, wrapping around to full brightness
+    pub fn next_brightness(&mut self) {
+        self.brightness =
+            match self.brightness {
+                b if b > 75 => 75,
+                b if b > 50 => 50,
+                b if b > 25 => 25,
+                _ => 100,
+            };
+    }
+
+    /// Change settings based on which buttons are held down while the board boots,
+    /// anything that isn't held keeps the saved setting
+    ///  - S1 held: tilt controls
+    ///  - S2 held: tilt controls, high sensitivity
+    ///  - S1 and S2 held: tilt controls, low sensitivity
+    ///  - joystick held left: 7-bag randomizer
+    ///  - joystick held right: history randomizer
+    ///  - joystick held down: seed mode
+    ///  - joystick button held: profiling
+    pub fn apply_boot_controls(&mut self, s1: bool, s2: bool, select: bool, joystick_x: u16, joystick_y: u16) {
+        if joystick_x < 50 {
+            self.randomizer = Strategy::Bag;
+        } else if joystick_x > 4000 {
+            self.randomizer = Strategy::History { rerolls: 4 };
+        }
+        self.seed_mode |= joystick_y < 10;
+        self.profile = select;
+        if s1 || s2 {
+            self.control_scheme = ControlScheme::Tilt;
+            self.tilt_sensitivity =
+                match (s1, s2) {
+                    (true, true) => Sensitivity::Low,
+                    (false, true) => Sensitivity::High,
+                    _ => Sensitivity::Medium,
+                };
+        }
+    }
+
+    /// The saved settings, or the defaults if nothing good was saved
+    pub fn load(eeprom: &impl Storage) -> Settings {
+        // older and newer records are different lengths, so read everything that could be one
+        let mut words = [0; eeprom::SETTINGS_WORDS];
+        eeprom.read(eeprom::SETTINGS_ADDRESS, &mut words);
+        Settings::from_words(&words).unwrap_or_default()
+    }
+
+    /// Save everything but `profile`, which only lasts until the next reset
+    pub fn save(&self, eeprom: &impl Storage) -> Result<(), eeprom::Error> {
+        eeprom.write(eeprom::SETTINGS_ADDRESS, &self.to_words())
+    }
+
+    fn to_words(self) -> [u32; RECORD_WORDS] {
+        let mut fields = [0; FIELDS];
+        fields[field::CONTROL_SCHEME] =
+            match self.control_scheme {
+                ControlScheme::Joystick => 0,
+                ControlScheme::Tilt => 1,
+            };
+        fields[field::TILT_SENSITIVITY] = encode_sensitivity(self.tilt_sensitivity);
+        let (randomizer, rerolls) =
+            match self.randomizer {
+                Strategy::Uniform => (0, 0),
+                Strategy::Bag => (1, 0),
+                Strategy::History { rerolls } => (2, rerolls),
+            };
+        fields[field::RANDOMIZER] = randomizer;
+        fields[field::REROLLS] = rerolls;
+        fields[field::SEED_MODE] = self.seed_mode as u8;
+        fields[field::FRAME_RATE] = self.frame_rate.hz() as u8;
+        fields[field::BRIGHTNESS] = self.brightness;
+        fields[field::JOYSTICK_SENSITIVITY] = encode_sensitivity(self.joystick_sensitivity);
+        fields[field::SWAP_ROTATION] = self.swap_rotation as u8;
+        fields[field::PALETTE] =
+            match self.palette {
+                Palette::Classic => 0,
+                Palette::Guideline => 1,
+            };
+
+        let mut words = [0; RECORD_WORDS];
+        words[0] = TAG | VERSION;
+        words[1] = FIELDS as u32;
+        for (i, byte) in fields.iter().enumerate() {
+            words[2 + i / 4] |= (*byte as u32) << (i % 4 * 8);
+        }
+        words[RECORD_WORDS - 1] = crc::crc32(&words[..RECORD_WORDS - 1]);
+        words
+    }
+
+    /// None if the record is garbage or from a newer version. Fields that are missing
+    /// (or hold something that makes no sense) get their defaults.
+    /// `words` can go past the end of the record, word 1 says how long it is.
+    fn from_words(words: &[u32]) -> Option<Settings> {
+        if words.len() < record_words(0) || words[0] & 0xFFFF_0000 != TAG {
+            return None;
+        }
+        // the CRC can't be checked until we know where it is, so the field count could be
+        // anything. Don't follow it past the words we've got.
+        let saved = words[1] as usize;
+        if saved > (words.len() - record_words(0)) * 4 {
+            return None;
+        }
+        let length = record_words(saved);
+        if words[length - 1] != crc::crc32(&words[..length - 1]) {
+            return None;
+        }
+        let version = words[0] & 0xFFFF;
+        if version > VERSION {
+            return None;
+        }
+
+        // fields past the end of the record didn't exist yet when it was saved, and
+        // fields we don't know about were added by newer firmware
+        let mut fields = [None; FIELDS];
+        for (i, field) in fields.iter_mut().enumerate().take(saved) {
+            *field = Some((words[2 + i / 4] >> (i % 4 * 8)) as u8);
+        }
+        migrate(version, &mut fields);
+
+        let defaults : Settings = Default::default();
+        // a field that wasn't saved decodes to nothing, just like one that doesn't make sense
+        let byte = |i: usize| fields[i].unwrap_or(0xFF);
+        Some(Settings {
+            control_scheme:
+                match byte(field::CONTROL_SCHEME) {
+                    0 => ControlScheme::Joystick,
+                    1 => ControlScheme::Tilt,
+                    _ => defaults.control_scheme,
+                },
+            tilt_sensitivity: decode_sensitivity(byte(field::TILT_SENSITIVITY))
+                                  .unwrap_or(defaults.tilt_sensitivity),
+            randomizer:
+                match byte(field::RANDOMIZER) {
+                    0 => Strategy::Uniform,
+                    1 => Strategy::Bag,
+                    2 => Strategy::History { rerolls: byte(field::REROLLS) },
+                    _ => defaults.randomizer,
+                },
+            seed_mode: decode_bool(byte(field::SEED_MODE)).unwrap_or(defaults.seed_mode),
+            profile: defaults.profile,
+            frame_rate:
+                match byte(field::FRAME_RATE) {
+                    50 => FrameRate::Fps50,
+                    60 => FrameRate::Fps60,
+                    70 => FrameRate::Fps70,
+                    _ => defaults.frame_rate,
+                },
+            brightness:
+                match byte(field::BRIGHTNESS) {
+                    // a dark screen is no good, so 0 doesn't count
+                    brightness @ 1..=100 => brightness,
+                    _ => defaults.brightness,
+                },
+            joystick_sensitivity: decode_sensitivity(byte(field::JOYSTICK_SENSITIVITY))
+                                      .unwrap_or(defaults.joystick_sensitivity),
+            swap_rotation: decode_bool(byte(field::SWAP_ROTATION)).unwrap_or(defaults.swap_rotation),
+            palette:
+                match byte(field::PALETTE) {
+                    0 => Palette::Classic,
+                    1 => Palette::Guideline,
+                    _ => defaults.palette,
+                },
+        })
+    }
+}
+
+/// "SE"
+const TAG : u32 = 0x5345_0000;
+/// Goes up when a field changes meaning, not when one is added
+const VERSION : u32 = 1;
+
+/// Where each field goes in the record. Only ever add to the end!
+mod field {
+    pub const CONTROL_SCHEME : usize = 0;
+    pub const TILT_SENSITIVITY : usize = 1;
+    pub const RANDOMIZER : usize = 2;
+    /// only means something for the history randomizer
+    pub const REROLLS : usize = 3;
+    pub const SEED_MODE : usize = 4;
+    /// in Hz
+    pub const FRAME_RATE : usize = 5;
+    /// in percent
+    pub const BRIGHTNESS : usize = 6;
+    pub const JOYSTICK_SENSITIVITY : usize = 7;
+    pub const SWAP_ROTATION : usize = 8;
+    pub const PALETTE : usize = 9;
+}
+
+const FIELDS : usize = 10;
+const RECORD_WORDS : usize = record_words(FIELDS);
+// there's only so much room set aside for settings
+const _ : () = assert!(RECORD_WORDS <= eeprom::SETTINGS_WORDS);
+
+/// Length of a record with `fields` fields: tag, field count, the fields 4 to a word, CRC
+const fn record_words(fields: usize) -> usize {
+    2 + fields.div_ceil(4) + 1
+}
+
+/// Convert fields saved by an older VERSION to what they mean now. None is a field that
+/// wasn't saved, which gets the default.
+fn migrate(version: u32, fields: &mut [Option<u8>; FIELDS]) {
+    // version 1 is the first one, so there's nothing to convert yet. When a field changes
+    // meaning it goes something like
+    //   if version < 2 { fields[field::X] = fields[field::X].map(old_to_new); }
+    let _ = (version, fields);
+}
+
+fn encode_sensitivity(sensitivity: Sensitivity) -> u8 {
+    match sensitivity {
+        Sensitivity::Low => 0,
+        Sensitivity::Medium => 1,
+        Sensitivity::High => 2,
+    }
+}
+
+fn decode_sensitivity(byte: u8) -> Option<Sensitivity> {
+    match byte {
+        0 => Some(Sensitivity::Low),
+        1 => Some(Sensitivity::Medium),
+        2 => Some(Sensitivity::High),
+        _ => None,
+    }
+}
+
+fn decode_bool(byte: u8) -> Option<bool> {
+    match byte {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom() -> Settings {
+        Settings {
+            control_scheme: ControlScheme::Tilt,
+            tilt_sensitivity: Sensitivity::High,
+            randomizer: Strategy::History { rerolls: 6 },
+            seed_mode: true,
+            profile: true,
+            frame_rate: FrameRate::Fps50,
+            brightness: 25,
+            joystick_sensitivity: Sensitivity::Low,
+            swap_rotation: true,
+            palette: Palette::Guideline,
+        }
+    }
+
+    fn assert_same(a: &Settings, b: &Settings) {
+        assert!(a.control_scheme == b.control_scheme);
+        assert!(a.tilt_sensitivity == b.tilt_sensitivity);
+        assert!(a.randomizer == b.randomizer);
+        assert_eq!(a.seed_mode, b.seed_mode);
+        assert_eq!(a.profile, b.profile);
+        assert!(a.frame_rate == b.frame_rate);
+        assert_eq!(a.brightness, b.brightness);
+        assert!(a.joystick_sensitivity == b.joystick_sensitivity);
+        assert_eq!(a.swap_rotation, b.swap_rotation);
+        assert!(a.palette == b.palette);
+    }
+
+    /// The settings block in the EEPROM with `record` at the start, and nothing written after it
+    fn block(record: &[u32]) -> [u32; eeprom::SETTINGS_WORDS] {
+        let mut words = [0xFFFF_FFFF; eeprom::SETTINGS_WORDS];
+        words[..record.len()].copy_from_slice(record);
+        words
+    }
+
+    /// A record the way it was saved by firmware that only knew about the first `fields` fields,
+    /// or about `fields - FIELDS` more (which are all 0x55)
+    fn other_record(settings: &Settings, fields: usize, version: u32) -> [u32; eeprom::SETTINGS_WORDS] {
+        let length = record_words(fields);
+        let mut words = block(&settings.to_words());
+        words[0] = TAG | version;
+        words[1] = fields as u32;
+        for i in 0..(length - 3) * 4 {
+            let byte =
+                if i >= fields { 0 }
+                else if i >= FIELDS { 0x55 }
+                else { (words[2 + i / 4] >> (i % 4 * 8)) & 0xFF };
+            words[2 + i / 4] = (words[2 + i / 4] & !(0xFF << (i % 4 * 8))) | byte << (i % 4 * 8);
+        }
+        words[length - 1] = crc::crc32(&words[..length - 1]);
+        // the EEPROM is blank after the record
+        for word in &mut words[length..] {
+            *word = 0xFFFF_FFFF;
+        }
+        words
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = custom();
+        let loaded = Settings::from_words(&settings.to_words()).unwrap();
+        // profiling is never saved
+        assert_same(&loaded, &Settings { profile: false, ..settings });
+
+        let defaults : Settings = Default::default();
+        assert_same(&Settings::from_words(&defaults.to_words()).unwrap(), &defaults);
+
+        // the way `load` reads it
+        assert_same(&Settings::from_words(&block(&settings.to_words())).unwrap(), &loaded);
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let eeprom = eeprom::Fake::new();
+        // nothing saved yet
+        assert_same(&Settings::load(&eeprom), &Settings::default());
+
+        // the high scores are saved right before the settings, make sure they stay out of the way
+        let mut high_scores = crate::highscore::HighScores::new();
+        high_scores.insert(crate::highscore::Entry { initials: *b"ABC", score: 100, level: 1, lines: 2 });
+        custom().save(&eeprom).unwrap();
+        high_scores.save(&eeprom).unwrap();
+        assert_same(&Settings::load(&eeprom), &Settings { profile: false, ..custom() });
+    }
+
+    #[test]
+    fn missing_fields_get_defaults() {
+        // saved before the joystick sensitivity, button swap and palette settings were added:
+        // 5 words, with the CRC in word 4
+        let words = other_record(&custom(), 7, 1);
+        assert_eq!(record_words(7), 5);
+        assert_eq!(words[4], crc::crc32(&words[..4]));
+        assert_eq!(words[5], 0xFFFF_FFFF);
+        let loaded = Settings::from_words(&other_record(&custom(), 7, 1)).unwrap();
+        let expected = Settings {
+            profile: false,
+            joystick_sensitivity: Sensitivity::Medium,
+            swap_rotation: false,
+            palette: Palette::Classic,
+            ..custom()
+        };
+        assert_same(&loaded, &expected);
+
+        // nothing at all
+        let defaults : Settings = Default::default();
+        assert_same(&Settings::from_words(&other_record(&custom(), 0, 1)).unwrap(), &defaults);
+    }
+
+    #[test]
+    fn extra_fields_are_ignored() {
+        // saved by newer firmware with the same VERSION, it just has more fields
+        // (and a longer record, the CRC is a word further along)
+        let words = other_record(&custom(), FIELDS + 3, VERSION);
+        assert_eq!(record_words(FIELDS + 3), RECORD_WORDS + 1);
+        assert_same(&Settings::from_words(&words).unwrap(), &Settings { profile: false, ..custom() });
+    }
+
+    #[test]
+    fn nonsense_fields_get_defaults() {
+        let mut words = custom().to_words();
+        // frame rate of 0xEE Hz, brightness 0
+        words[2 + field::FRAME_RATE / 4] |= 0xEE << (field::FRAME_RATE % 4 * 8);
+        words[2 + field::BRIGHTNESS / 4] &= !(0xFF << (field::BRIGHTNESS % 4 * 8));
+        words[RECORD_WORDS - 1] = crc::crc32(&words[..RECORD_WORDS - 1]);
+
+        let loaded = Settings::from_words(&words).unwrap();
+        assert!(loaded.frame_rate == FrameRate::Fps70);
+        assert_eq!(loaded.brightness, 100);
+        assert!(loaded.palette == Palette::Guideline);
+    }
+
+    #[test]
+    fn bad_records_are_rejected() {
+        let good = custom().to_words();
+
+        // a blank EEPROM is all ones
+        assert!(Settings::from_words(&[0xFFFF_FFFF; RECORD_WORDS]).is_none());
+
+        let mut corrupted = good;
+        corrupted[2] ^= 0x100;
+        assert!(Settings::from_words(&corrupted).is_none());
+
+        let mut wrong_tag = good;
+        wrong_tag[0] = 0x4853_0001;
+        wrong_tag[RECORD_WORDS - 1] = crc::crc32(&wrong_tag[..RECORD_WORDS - 1]);
+        assert!(Settings::from_words(&wrong_tag).is_none());
+
+        // a field count that doesn't match where the CRC is
+        let mut short = good;
+        short[1] = 7;
+        assert!(Settings::from_words(&block(&short)).is_none());
+
+        // a field count that runs off the end
+        let mut huge = block(&good);
+        huge[1] = 0xFFFF_FFFF;
+        assert!(Settings::from_words(&huge).is_none());
+        let mut too_long = block(&good);
+        too_long[1] = (eeprom::SETTINGS_WORDS as u32 - 3) * 4 + 1;
+        assert!(Settings::from_words(&too_long).is_none());
+        assert!(Settings::from_words(&good[..2]).is_none());
+
+        // a version from the future could mean anything
+        assert!(Settings::from_words(&other_record(&custom(), FIELDS, VERSION + 1)).is_none());
+    }
+}
@@ -0,0 +1,100 @@
+// How fast the game runs, separately from how often the screen is updated.
+//
+// fourtris does everything (gravity, how fast held pieces slide, line clear delays...) in
+// calls to `run_loop`, and was tuned with one call every frame at 70 fps. Here every level
+// gets its own number of `run_loop` calls per second, and each frame makes however many
+// calls are due. So the frame rate can be changed without changing the game speed, and the
+// speed can be tuned without touching fourtris.
+
+/// The rate fourtris was tuned for
+pub const BASE_TICKS_PER_SECOND : u32 = 70;
+
+/// The game speed for each level, starting at level 1: run_loop calls per second.
+/// Levels past the end of the table use the last entry.
+///
+/// This isn't gravity, it speeds up the whole game: held pieces slide faster and line clears
+/// and lock delays get shorter too, on top of whatever fourtris does to gravity by itself as
+/// the level goes up. So the curve is gentle, level 10 runs 1.5 times as fast as level 1.
+const GAME_SPEED : [u32; 10] = [
+    BASE_TICKS_PER_SECOND,
+    72,
+    74,
+    77,
+    80,
+    84,
+    88,
+    93,
+    99,
+    105,
+];
+
+/// The game speed at `level`, in run_loop calls per second
+pub fn ticks_per_second(level: usize) -> u32 {
+    let index = if level == 0 { 0 } else { level - 1 };
+    GAME_SPEED[index.min(GAME_SPEED.len() - 1)]
+}
+
+/// Spreads game ticks (run_loop calls) evenly over frames
+pub struct Speed {
+    /// leftover ticks, in 1/frame_rate ticks
+    accumulator: u32,
+}
+
+impl Speed {
+    pub fn new() -> Speed {
+        Speed {
+            accumulator: 0,
+        }
+    }
+
+    /// Number of times run_loop should be called this frame
+    pub fn ticks(&mut self, level: usize, frame_rate: u32) -> u32 {
+        self.accumulator += ticks_per_second(level);
+        let ticks = self.accumulator / frame_rate;
+        self.accumulator %= frame_rate;
+        ticks
+    }
+
+    /// Forget about leftover ticks, for when a new game starts
+    pub fn reset(&mut self) {
+        self.accumulator = 0;
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Speed {
+        Speed::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_second_gets_all_its_ticks() {
+        for frame_rate in [50, 60, 70] {
+            for level in 0..=GAME_SPEED.len() + 2 {
+                let mut speed = Speed::new();
+                let per_second = ticks_per_second(level);
+                for second in 1..=3 {
+                    let ticks : Vec<u32> = (0..frame_rate).map(|_| speed.ticks(level, frame_rate)).collect();
+                    assert_eq!(ticks.iter().sum::<u32>(), per_second, "{} fps, level {}, second {}", frame_rate, level, second);
+                    // spread out evenly, never bunched up
+                    let fewest = per_second / frame_rate;
+                    assert!(ticks.iter().all(|t| *t == fewest || *t == fewest + 1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_game_never_slows_down() {
+        assert_eq!(ticks_per_second(0), BASE_TICKS_PER_SECOND);
+        assert_eq!(ticks_per_second(1), BASE_TICKS_PER_SECOND);
+        for level in 1..20 {
+            assert!(ticks_per_second(level + 1) >= ticks_per_second(level));
+        }
+        assert_eq!(ticks_per_second(100), GAME_SPEED[GAME_SPEED.len() - 1]);
+    }
+}
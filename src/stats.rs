@@ -0,0 +1,278 @@
+use fourtris::rng::Rng;
+
+use crate::clock::SYSTEM_CLOCK_HZ;
+use crate::crc;
+use crate::eeprom::{self, Storage};
+
+// Statistics over every game ever played on the board, kept in the EEPROM.
+//
+// Pieces are counted as they're dealt, by wrapping the rng the game gets (like GameLog does).
+// The numbers the game gets map to pieces in the order of PIECE_NAMES, the same order as
+// TetriminoType. Dealt includes the next piece the game keeps in its back pocket, so the count
+// can be one ahead of what actually hit the board.
+//
+// In the EEPROM the stats take up RECORD_WORDS words:
+//   word 0          VERSION
+//   word 1          games played
+//   word 2          lines
+//   word 3          best level
+//   word 4          play time, in seconds
+//   words 5 - 11    pieces dealt, in PIECE_NAMES order
+//   word 12         CRC of words 0 - 11
+// If the version or CRC doesn't match, everything starts over at zero.
+
+pub const PIECES : usize = 7;
+/// One letter for each piece, in the order the rng deals them
+pub const PIECE_NAMES : [u8; PIECES] = *b"IOJLSZT";
+
+/// "ST" and the layout version
+const VERSION : u32 = 0x5354_0001;
+const RECORD_WORDS : usize = 5 + PIECES + 1;
+const _: () = assert!(RECORD_WORDS <= eeprom::STATS_WORDS);
+
+/// One game's worth of stats
+#[derive(Clone, Copy)]
+pub struct GameStats {
+    pub pieces: [u32; PIECES],
+    /// clock ticks left over that don't add up to a whole second yet
+    ticks: u32,
+    /// time spent actually playing (not paused)
+    pub seconds: u32,
+}
+
+impl GameStats {
+    pub fn new() -> GameStats {
+        GameStats {
+            pieces: [0; PIECES],
+            ticks: 0,
+            seconds: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = GameStats::new();
+    }
+
+    /// True once the game has been dealt anything, so it counts as a game
+    pub fn started(&self) -> bool {
+        self.dealt() > 0
+    }
+
+    /// Pieces dealt so far, of every kind
+    pub fn dealt(&self) -> u32 {
+        self.pieces.iter().fold(0, |total, count| total.saturating_add(*count))
+    }
+
+    /// Wraps `rng` so every piece the game gets is counted, use it for Game::new and every
+    /// run_loop call
+    pub fn counter<'b, R: Rng>(&'b mut self, rng: &'b mut R) -> Counter<'b, R> {
+        Counter { stats: self, rng }
+    }
+
+    /// Count `elapsed_ticks` clock ticks of playing time
+    pub fn add_time(&mut self, elapsed_ticks: u32) {
+        self.ticks += elapsed_ticks;
+        while self.ticks >= SYSTEM_CLOCK_HZ {
+            self.ticks -= SYSTEM_CLOCK_HZ;
+            self.seconds = self.seconds.saturating_add(1);
+        }
+    }
+}
+
+impl Default for GameStats {
+    fn default() -> GameStats {
+        GameStats::new()
+    }
+}
+
+/// Passes along everything from the random number generator, counting the pieces
+pub struct Counter<'b, R> {
+    stats: &'b mut GameStats,
+    rng: &'b mut R,
+}
+
+impl<R: Rng> Rng for Counter<'_, R> {
+    fn next(&mut self) -> usize {
+        let value = self.rng.next();
+        if let Some(count) = self.stats.pieces.get_mut(value) {
+            *count = count.saturating_add(1);
+        }
+        value
+    }
+}
+
+/// Everything, over every game
+pub struct LifetimeStats {
+    pub games: u32,
+    pub lines: u32,
+    pub best_level: u32,
+    pub seconds: u32,
+    pub pieces: [u32; PIECES],
+}
+
+impl LifetimeStats {
+    pub fn new() -> LifetimeStats {
+        LifetimeStats {
+            games: 0,
+            lines: 0,
+            best_level: 0,
+            seconds: 0,
+            pieces: [0; PIECES],
+        }
+    }
+
+    /// Read the stats out of the EEPROM, or start over if there aren't good ones there
+    pub fn load(eeprom: &impl Storage) -> LifetimeStats {
+        let mut words = [0; RECORD_WORDS];
+        eeprom.read(eeprom::STATS_ADDRESS, &mut words);
+        LifetimeStats::from_words(&words).unwrap_or_default()
+    }
+
+    pub fn save(&self, eeprom: &impl Storage) -> Result<(), eeprom::Error> {
+        eeprom.write(eeprom::STATS_ADDRESS, &self.to_words())
+    }
+
+    /// Add in a game that just ended
+    pub fn add_game(&mut self, game: &GameStats, lines: u32, level: usize) {
+        self.games = self.games.saturating_add(1);
+        self.lines = self.lines.saturating_add(lines);
+        self.best_level = self.best_level.max(level as u32);
+        self.seconds = self.seconds.saturating_add(game.seconds);
+        for (total, count) in self.pieces.iter_mut().zip(game.pieces.iter()) {
+            *total = total.saturating_add(*count);
+        }
+    }
+
+    fn to_words(&self) -> [u32; RECORD_WORDS] {
+        let mut words = [0; RECORD_WORDS];
+        words[0] = VERSION;
+        words[1] = self.games;
+        words[2] = self.lines;
+        words[3] = self.best_level;
+        words[4] = self.seconds;
+        words[5..5 + PIECES].copy_from_slice(&self.pieces);
+        words[RECORD_WORDS - 1] = crc::crc32(&words[..RECORD_WORDS - 1]);
+        words
+    }
+
+    fn from_words(words: &[u32; RECORD_WORDS]) -> Option<LifetimeStats> {
+        if words[0] != VERSION || words[RECORD_WORDS - 1] != crc::crc32(&words[..RECORD_WORDS - 1]) {
+            return None;
+        }
+        let mut pieces = [0; PIECES];
+        pieces.copy_from_slice(&words[5..5 + PIECES]);
+        Some(LifetimeStats {
+            games: words[1],
+            lines: words[2],
+            best_level: words[3],
+            seconds: words[4],
+            pieces,
+        })
+    }
+}
+
+impl Default for LifetimeStats {
+    fn default() -> LifetimeStats {
+        LifetimeStats::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played() -> LifetimeStats {
+        let mut game = GameStats::new();
+        let mut dealer = [0, 6, 6, 3, 2, 1, 5, 4, 6].iter().copied();
+        let mut rng = Dealer(&mut dealer);
+        for _ in 0..9 {
+            game.counter(&mut rng).next();
+        }
+        for _ in 0..90 {
+            game.add_time(SYSTEM_CLOCK_HZ);
+        }
+
+        let mut lifetime = LifetimeStats::new();
+        lifetime.add_game(&game, 12, 3);
+        lifetime.add_game(&game, 30, 2);
+        lifetime
+    }
+
+    /// Deals whatever the iterator has
+    struct Dealer<'a>(&'a mut dyn Iterator<Item = usize>);
+
+    impl Rng for Dealer<'_> {
+        fn next(&mut self) -> usize {
+            self.0.next().unwrap()
+        }
+    }
+
+    #[test]
+    fn games_add_up() {
+        let lifetime = played();
+        assert_eq!(lifetime.games, 2);
+        assert_eq!(lifetime.lines, 42);
+        assert_eq!(lifetime.best_level, 3);
+        assert_eq!(lifetime.seconds, 180);
+        assert_eq!(lifetime.pieces, [2, 2, 2, 2, 2, 2, 6]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let eeprom = eeprom::Fake::new();
+        // nothing saved yet
+        assert_eq!(LifetimeStats::load(&eeprom).games, 0);
+
+        played().save(&eeprom).unwrap();
+        let loaded = LifetimeStats::load(&eeprom);
+        assert_eq!(loaded.to_words(), played().to_words());
+        assert_eq!(loaded.games, 2);
+    }
+
+    #[test]
+    fn bad_records_start_over() {
+        let words = played().to_words();
+        assert!(LifetimeStats::from_words(&words).is_some());
+
+        // any flipped bit fails the CRC
+        for i in 1..RECORD_WORDS {
+            let mut corrupt = words;
+            corrupt[i] ^= 0x8000;
+            assert!(LifetimeStats::from_words(&corrupt).is_none(), "word {}", i);
+        }
+
+        // another layout, even with a good CRC
+        let mut other = words;
+        other[0] = VERSION + 1;
+        other[RECORD_WORDS - 1] = crc::crc32(&other[..RECORD_WORDS - 1]);
+        assert!(LifetimeStats::from_words(&other).is_none());
+
+        // and that's what gets loaded
+        let eeprom = eeprom::Fake::new();
+        eeprom.write(eeprom::STATS_ADDRESS, &other).unwrap();
+        assert_eq!(LifetimeStats::load(&eeprom).games, 0);
+    }
+
+    #[test]
+    fn time_adds_up_in_seconds() {
+        let mut game = GameStats::new();
+        // a frame at 70 fps, 69 times is still short of a second
+        let frame = SYSTEM_CLOCK_HZ / 70;
+        for _ in 0..69 {
+            game.add_time(frame);
+        }
+        assert_eq!(game.seconds, 0);
+        game.add_time(frame);
+        game.add_time(frame);
+        assert_eq!(game.seconds, 1);
+        // a long stretch all at once, with the leftover from before still counted
+        game.add_time(SYSTEM_CLOCK_HZ * 3 + SYSTEM_CLOCK_HZ / 2);
+        assert_eq!(game.seconds, 4);
+        game.add_time(SYSTEM_CLOCK_HZ / 2);
+        assert_eq!(game.seconds, 5);
+
+        game.reset();
+        assert_eq!(game.seconds, 0);
+        assert!(!game.started());
+    }
+}
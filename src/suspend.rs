@@ -0,0 +1,552 @@
+use fourtris::game::Input;
+use fourtris::rng::Rng;
+
+use crate::crc::Crc32;
+use crate::flash::{self, Storage};
+use crate::input;
+
+// Saving a game in progress, so it can be picked back up after the power goes out.
+//
+// Game keeps the board, the falling piece and everything else to itself, so there's no way to
+// copy it out and put it back. It's completely predictable though: the same pieces and the same
+// input always lead to the same game. So instead of the game itself, the log keeps
+//  - every number the game got from the random number generator (the deals)
+//  - the input for every run_loop call, as runs of identical input
+// and resuming plays the whole game over again, quietly, from the start.
+//
+// The log itself lives in the reserved flash (see flash.rs). RAM only has what happened since the
+// last save, and saving adds that onto the end of the log as a new segment. That happens when the
+// game is paused, when the board is about to go to sleep, and whenever the RAM starts to fill up
+// (see `wants_save`), so how long a game can be saved for only depends on the flash.
+// In flash it looks like:
+//   word 0       MAGIC, written along with the first segment, so a save that got cut off doesn't count
+//   then         one segment per save:
+//     word 0       number of deals in the bottom half, number of runs in the top half
+//     word 1       word 0 inverted, so a header that got cut off isn't taken for a real one
+//     word 2       score, for the continue screen
+//     word 3       level, same
+//     then         the deals, 4 to a word, least significant byte first
+//     then         the runs, 2 to a word, bottom half first
+//     last word    CRC of the segment
+//   then         blank flash (all ones), where the next segment goes
+//
+// A segment with a bad CRC got cut off when the power went out, so the game carried on from the
+// segment before it, and it's skipped. A header that got cut off hides where the rest is, so the
+// game carries on from there but can't be saved again. Flash is erased a page at a time as the log
+// gets to it, so there's always a blank word after the last segment.
+//
+// A game that goes on long enough fills up the flash (it takes over an hour), and can't be saved
+// after that.
+
+/// "SG" and the layout version
+const MAGIC : u32 = 0x5347_0002;
+const SEGMENT_HEADER_WORDS : usize = 4;
+/// The first segment goes right after MAGIC
+const LOG_START : u32 = flash::RESERVED_START + 4;
+
+/// Deals kept in RAM until the next save, a piece every second or two makes this a few minutes
+const BUFFER_DEALS : usize = 256;
+/// Input changes a few times a second while somebody is playing, and there are 70 run_loop
+/// calls a second (see speed.rs), so this is about a minute
+const BUFFER_RUNS : usize = 256;
+
+// A run is the input bits (see input::encode) on top of how many run_loop calls they lasted
+const RUN_LENGTH_BITS : u32 = 11;
+const MAX_RUN_LENGTH : u16 = (1 << RUN_LENGTH_BITS) - 1;
+
+/// What the continue screen shows about a saved game
+#[derive(Clone, Copy)]
+pub struct Summary {
+    pub score: u32,
+    pub level: usize,
+}
+
+pub struct GameLog {
+    deals: [u8; BUFFER_DEALS],
+    deal_count: usize,
+    runs: [u16; BUFFER_RUNS],
+    run_count: usize,
+    /// where the next segment goes
+    end: u32,
+    /// the flash from `end` up to here has been erased for this game
+    erased_end: u32,
+    /// something didn't fit or the flash gave up, the game can't be saved anymore
+    full: bool,
+    /// something was logged since the last save
+    unsaved: bool,
+}
+
+impl GameLog {
+    pub fn new() -> GameLog {
+        GameLog {
+            deals: [0; BUFFER_DEALS],
+            deal_count: 0,
+            runs: [0; BUFFER_RUNS],
+            run_count: 0,
+            end: LOG_START,
+            erased_end: flash::RESERVED_START,
+            full: false,
+            unsaved: false,
+        }
+    }
+
+    /// Forget everything, for a new game
+    pub fn clear(&mut self) {
+        self.deal_count = 0;
+        self.run_count = 0;
+        self.end = LOG_START;
+        self.erased_end = flash::RESERVED_START;
+        self.full = false;
+        self.unsaved = false;
+    }
+
+    /// True once the game can't be saved anymore
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    /// True if the game has moved on since it was last saved (or loaded)
+    pub fn has_unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// True when the RAM is getting full and it's time to save, even in the middle of a game.
+    /// There's still room for a few frames after this.
+    pub fn wants_save(&self) -> bool {
+        !self.full && (self.deal_count >= BUFFER_DEALS * 3 / 4 || self.run_count >= BUFFER_RUNS * 3 / 4)
+    }
+
+    /// Wraps `rng` so everything the game gets from it is logged, use it for Game::new and
+    /// every run_loop call
+    pub fn recorder<'b, R: Rng>(&'b mut self, rng: &'b mut R) -> Recorder<'b, R> {
+        Recorder { log: self, rng }
+    }
+
+    /// Call after every run_loop call, with the input it got
+    pub fn record_input(&mut self, input: &Input) {
+        if self.full {
+            return;
+        }
+        self.unsaved = true;
+        let bits = (input::encode(input) as u16) << RUN_LENGTH_BITS;
+        if self.run_count > 0 {
+            let last = &mut self.runs[self.run_count - 1];
+            if *last & !MAX_RUN_LENGTH == bits && *last & MAX_RUN_LENGTH < MAX_RUN_LENGTH {
+                *last += 1;
+                return;
+            }
+        }
+        if self.run_count == BUFFER_RUNS {
+            self.full = true;
+            return;
+        }
+        self.runs[self.run_count] = bits | 1;
+        self.run_count += 1;
+    }
+
+    /// Add everything since the last save onto the saved game in flash. The first save of a game
+    /// replaces whatever game was saved before.
+    /// Takes 10-30 ms when the RAM is full, the CPU stalls while flash is being written.
+    /// If the flash fails the game can't be saved anymore.
+    pub fn save(&mut self, flash: &impl Storage, summary: Summary) -> Result<(), flash::Error> {
+        if self.full {
+            return Ok(());
+        }
+        let segment = Segment { address: self.end, deal_count: self.deal_count, run_count: self.run_count };
+        if segment.end() > flash::RESERVED_END {
+            self.full = true;
+            return Ok(());
+        }
+
+        let result = self.write_segment(flash, &segment, summary);
+        if result.is_err() {
+            // there's no telling what made it into flash, so nothing can go after it
+            self.full = true;
+            return result;
+        }
+        self.end = segment.end();
+        self.deal_count = 0;
+        self.run_count = 0;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    fn write_segment(&mut self, flash: &impl Storage, segment: &Segment, summary: Summary) -> Result<(), flash::Error> {
+        let header = self.deal_count as u32 | (self.run_count as u32) << 16;
+        let deals = self.deals[..self.deal_count].chunks(4)
+                        .map(|deals| deals.iter().rev().fold(0, |word, deal| word << 8 | *deal as u32));
+        let runs = self.runs[..self.run_count].chunks(2)
+                       .map(|runs| runs.iter().rev().fold(0, |word, run| word << 16 | *run as u32));
+
+        // everything up to the next header has to be blank before any of it gets written: if
+        // the power goes out halfway through, the next save goes after this one and the next
+        // header has to read as blank until then
+        erase_through(flash, &mut self.erased_end, segment.end().min(flash::RESERVED_END - 4))?;
+
+        let mut address = segment.address;
+        let mut crc = Crc32::new();
+        let words = IntoIterator::into_iter([header, !header, summary.score, summary.level as u32]).chain(deals).chain(runs);
+        for word in words {
+            crc.update(word);
+            flash.write(address, word)?;
+            address += 4;
+        }
+        flash.write(address, crc.finish())?;
+
+        // the first segment is in, now it counts
+        if segment.address == LOG_START {
+            flash.write(flash::RESERVED_START, MAGIC)?;
+        }
+        Ok(())
+    }
+
+    /// Find the end of the saved game, so it can be carried on with. Returns false (and leaves
+    /// the log empty) if there isn't a good one. Use `saved_deals` and `saved_inputs` to replay it.
+    pub fn load(&mut self, flash: &impl Storage) -> bool {
+        self.clear();
+        if flash.read(flash::RESERVED_START) != MAGIC {
+            return false;
+        }
+        let mut segments = Segments::new(flash);
+        let found = segments.by_ref().count() > 0;
+        if !found {
+            return false;
+        }
+        self.end = segments.address;
+        // everything up to the end was erased when it was written, along with the rest of its page
+        self.erased_end = ((self.end & !(flash::PAGE_SIZE - 1)) + flash::PAGE_SIZE).min(flash::RESERVED_END);
+        self.full = segments.broken;
+        true
+    }
+}
+
+impl Default for GameLog {
+    fn default() -> GameLog {
+        GameLog::new()
+    }
+}
+
+/// Erase the pages from `erased_end` up to the one with `address` in it
+fn erase_through(flash: &impl Storage, erased_end: &mut u32, address: u32) -> Result<(), flash::Error> {
+    while address >= *erased_end {
+        flash.erase_page(*erased_end)?;
+        *erased_end += flash::PAGE_SIZE;
+    }
+    Ok(())
+}
+
+/// Where a segment is, and what's in it
+#[derive(Clone, Copy)]
+struct Segment {
+    address: u32,
+    deal_count: usize,
+    run_count: usize,
+}
+
+impl Segment {
+    fn deals_address(&self) -> u32 {
+        self.address + SEGMENT_HEADER_WORDS as u32 * 4
+    }
+
+    fn runs_address(&self) -> u32 {
+        self.deals_address() + self.deal_count.div_ceil(4) as u32 * 4
+    }
+
+    fn crc_address(&self) -> u32 {
+        self.runs_address() + self.run_count.div_ceil(2) as u32 * 4
+    }
+
+    /// Where the next segment goes
+    fn end(&self) -> u32 {
+        self.crc_address() + 4
+    }
+
+    fn deal(&self, flash: &impl Storage, index: usize) -> u8 {
+        (flash.read(self.deals_address() + index as u32 / 4 * 4) >> (index % 4 * 8)) as u8
+    }
+
+    fn run(&self, flash: &impl Storage, index: usize) -> u16 {
+        (flash.read(self.runs_address() + index as u32 / 2 * 4) >> (index % 2 * 16)) as u16
+    }
+
+    fn summary(&self, flash: &impl Storage) -> Summary {
+        Summary {
+            score: flash.read(self.address + 8),
+            level: flash.read(self.address + 12) as usize,
+        }
+    }
+}
+
+/// The segments of the saved game that made it into flash in one piece
+struct Segments<'b, F> {
+    flash: &'b F,
+    /// where the next one should be
+    address: u32,
+    /// a header got cut off, so the rest of the log can't be found
+    broken: bool,
+}
+
+impl<'b, F: Storage> Segments<'b, F> {
+    fn new(flash: &'b F) -> Segments<'b, F> {
+        Segments { flash, address: LOG_START, broken: false }
+    }
+}
+
+impl<F: Storage> Iterator for Segments<'_, F> {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        loop {
+            if self.address + SEGMENT_HEADER_WORDS as u32 * 4 > flash::RESERVED_END {
+                return None;
+            }
+            let header = self.flash.read(self.address);
+            if header == 0xFFFF_FFFF {
+                // blank, that's the end
+                return None;
+            }
+            let segment = Segment {
+                address: self.address,
+                deal_count: (header & 0xFFFF) as usize,
+                run_count: (header >> 16) as usize,
+            };
+            if self.flash.read(self.address + 4) != !header
+                || segment.deal_count > BUFFER_DEALS
+                || segment.run_count > BUFFER_RUNS
+                || segment.end() > flash::RESERVED_END {
+                self.broken = true;
+                return None;
+            }
+            self.address = segment.end();
+
+            let mut crc = Crc32::new();
+            let mut address = segment.address;
+            while address < segment.crc_address() {
+                crc.update(self.flash.read(address));
+                address += 4;
+            }
+            if self.flash.read(address) == crc.finish() {
+                return Some(segment);
+            }
+            // cut off, the game went on from the segment before this one
+        }
+    }
+}
+
+/// The game saved in flash, if there's a good one
+pub fn saved_game(flash: &impl Storage) -> Option<Summary> {
+    if flash.read(flash::RESERVED_START) != MAGIC {
+        return None;
+    }
+    Segments::new(flash).last().map(|segment| segment.summary(flash))
+}
+
+/// Get rid of the saved game, if there is one. Only the first page needs to go, it has MAGIC.
+pub fn discard(flash: &impl Storage) -> Result<(), flash::Error> {
+    if flash.read(flash::RESERVED_START) == MAGIC {
+        flash.erase_page(flash::RESERVED_START)?;
+    }
+    Ok(())
+}
+
+/// Hands out the saved game's deals again, in the same order
+pub fn saved_deals<F: Storage>(flash: &F) -> Deals<'_, F> {
+    Deals { segments: Segments::new(flash), segment: None, next: 0 }
+}
+
+/// The saved game's input, one for each run_loop call
+pub fn saved_inputs<F: Storage>(flash: &F) -> impl Iterator<Item = Input> + '_ {
+    Segments::new(flash).flat_map(move |segment| {
+        (0..segment.run_count).flat_map(move |index| {
+            let run = segment.run(flash, index);
+            let input_bits = (run >> RUN_LENGTH_BITS) as u8;
+            (0..run & MAX_RUN_LENGTH).map(move |_| input::decode_input(input_bits))
+        })
+    })
+}
+
+/// Passes along everything from the random number generator, logging it on the way
+pub struct Recorder<'b, R> {
+    log: &'b mut GameLog,
+    rng: &'b mut R,
+}
+
+impl<R: Rng> Rng for Recorder<'_, R> {
+    fn next(&mut self) -> usize {
+        let value = self.rng.next();
+        let log = &mut *self.log;
+        if !log.full {
+            if log.deal_count == BUFFER_DEALS {
+                log.full = true;
+            } else {
+                log.deals[log.deal_count] = value as u8;
+                log.deal_count += 1;
+                log.unsaved = true;
+            }
+        }
+        value
+    }
+}
+
+/// See `saved_deals`
+pub struct Deals<'b, F> {
+    segments: Segments<'b, F>,
+    segment: Option<Segment>,
+    /// the next deal in `segment`
+    next: usize,
+}
+
+impl<F: Storage> Rng for Deals<'_, F> {
+    fn next(&mut self) -> usize {
+        loop {
+            if let Some(segment) = self.segment {
+                if self.next < segment.deal_count {
+                    self.next += 1;
+                    return segment.deal(self.segments.flash, self.next - 1) as usize;
+                }
+            }
+            // on to the next segment. Running out would mean the replay went differently than
+            // the game, which can't happen
+            match self.segments.next() {
+                Some(segment) => self.segment = Some(segment),
+                None => return 0,
+            }
+            self.next = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flash::Fake;
+
+    /// Deals 1, 2, ... 250 and around again
+    struct Dealer(usize);
+
+    impl Rng for Dealer {
+        fn next(&mut self) -> usize {
+            self.0 = self.0 % 250 + 1;
+            self.0
+        }
+    }
+
+    /// What a game got, as frame bytes and deals
+    #[derive(Clone, Default, PartialEq, Debug)]
+    struct Played {
+        deals: Vec<usize>,
+        inputs: Vec<u8>,
+    }
+
+    /// Play `frames` run_loop calls into `log`, saving whenever it wants to. `start` changes
+    /// which input and deals come when.
+    fn play(log: &mut GameLog, flash: &Fake, dealer: &mut Dealer, start: usize, frames: usize, played: &mut Played) {
+        for frame in start..start + frames {
+            if frame % 37 == 0 {
+                played.deals.push(log.recorder(dealer).next());
+            }
+            let bits = (frame / 13 % 32) as u8;
+            log.record_input(&input::decode_input(bits));
+            played.inputs.push(bits);
+            if log.wants_save() {
+                log.save(flash, Summary { score: frame as u32, level: 1 }).unwrap();
+            }
+        }
+    }
+
+    /// Everything the saved game would replay
+    fn replay(flash: &Fake) -> Played {
+        let mut deals = saved_deals(flash);
+        Played {
+            // the dealer never deals 0, that's what Deals says when it runs out
+            deals: core::iter::from_fn(|| Some(deals.next())).take_while(|deal| *deal != 0).collect(),
+            inputs: saved_inputs(flash).map(|input| input::encode(&input)).collect(),
+        }
+    }
+
+    /// Play a game and save it, in a few segments
+    fn saved(flash: &Fake) -> (GameLog, Played) {
+        let mut log = GameLog::new();
+        let mut played = Played::default();
+        play(&mut log, flash, &mut Dealer(0), 0, 20_000, &mut played);
+        log.save(flash, Summary { score: 100, level: 2 }).unwrap();
+        (log, played)
+    }
+
+    #[test]
+    fn save_load_and_replay() {
+        let flash = Fake::new();
+        assert!(saved_game(&flash).is_none());
+        assert!(!GameLog::new().load(&flash));
+
+        let (log, mut played) = saved(&flash);
+        assert!(!log.has_unsaved() && !log.is_full());
+        assert!(played.deals.len() > BUFFER_DEALS && played.inputs.len() > 10_000);
+        assert_eq!(saved_game(&flash).unwrap().score, 100);
+        assert_eq!(replay(&flash), played);
+
+        // after a reset the game picks up where it left off, and saves after the first part
+        let mut log = GameLog::new();
+        assert!(log.load(&flash));
+        let mut dealer = Dealer(*played.deals.last().unwrap());
+        play(&mut log, &flash, &mut dealer, 20_000, 5_000, &mut played);
+        log.save(&flash, Summary { score: 200, level: 3 }).unwrap();
+        let summary = saved_game(&flash).unwrap();
+        assert_eq!((summary.score, summary.level), (200, 3));
+        assert_eq!(replay(&flash), played);
+
+        discard(&flash).unwrap();
+        assert!(saved_game(&flash).is_none());
+    }
+
+    #[test]
+    fn a_segment_that_got_cut_off_is_skipped() {
+        let flash = Fake::new();
+        let (mut log, played) = saved(&flash);
+
+        // the power goes out right before the last segment's CRC is written
+        let mut more = played.clone();
+        let mut dealer = Dealer(*played.deals.last().unwrap());
+        play(&mut log, &flash, &mut dealer, 20_000, 500, &mut more);
+        let segment = Segment { address: log.end, deal_count: log.deal_count, run_count: log.run_count };
+        log.save(&flash, Summary { score: 150, level: 2 }).unwrap();
+        flash.set(segment.crc_address(), 0xFFFF_FFFF);
+
+        // the game carries on from the save before
+        assert_eq!(saved_game(&flash).unwrap().score, 100);
+        assert_eq!(replay(&flash), played);
+        let mut log = GameLog::new();
+        assert!(log.load(&flash));
+        assert!(!log.is_full());
+        // and the next save goes after the broken segment
+        assert_eq!(log.end, segment.end());
+
+        let mut dealer = Dealer(*played.deals.last().unwrap());
+        let mut again = played.clone();
+        play(&mut log, &flash, &mut dealer, 30_000, 500, &mut again);
+        log.save(&flash, Summary { score: 160, level: 2 }).unwrap();
+        assert_eq!(saved_game(&flash).unwrap().score, 160);
+        assert_eq!(replay(&flash), again);
+    }
+
+    #[test]
+    fn a_header_that_got_cut_off_fills_the_log() {
+        let flash = Fake::new();
+        let (mut log, played) = saved(&flash);
+
+        // the power goes out after the first word of the next segment
+        let mut dealer = Dealer(*played.deals.last().unwrap());
+        let mut more = played.clone();
+        play(&mut log, &flash, &mut dealer, 20_000, 500, &mut more);
+        let address = log.end;
+        log.save(&flash, Summary { score: 150, level: 2 }).unwrap();
+        flash.set(address + 4, 0xFFFF_FFFF);
+
+        // the game up to there can still be played, but there's no telling where to save next
+        let mut log = GameLog::new();
+        assert!(log.load(&flash));
+        assert!(log.is_full());
+        assert_eq!(saved_game(&flash).unwrap().score, 100);
+        assert_eq!(replay(&flash), played);
+    }
+}
@@ -0,0 +1,77 @@
+// Helpers for the tests that play whole games, see seed.rs and input.rs
+use fourtris::game::{Game, GameState, Input};
+use fourtris::game_renderer::{GameRenderer, TetriminoType};
+use fourtris::rng::Rng;
+
+use crate::input::{self, InputSource};
+
+/// One thing the game asked the renderer to draw
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Draw {
+    /// the piece is 0 - 6 in the order of TetriminoType, 7 is empty
+    Block { x: u8, y: u8, piece: u8 },
+    Score(u32),
+    Level(usize),
+}
+
+/// A GameRenderer that writes down everything it's asked to draw
+#[derive(Default)]
+pub struct Drawing {
+    pub draws: Vec<Draw>,
+}
+
+impl GameRenderer for Drawing {
+    fn draw_block(&mut self, x: u8, y: u8, t: TetriminoType) {
+        let piece =
+            match t {
+                TetriminoType::I => 0,
+                TetriminoType::O => 1,
+                TetriminoType::J => 2,
+                TetriminoType::L => 3,
+                TetriminoType::S => 4,
+                TetriminoType::Z => 5,
+                TetriminoType::T => 6,
+                TetriminoType::EmptySpace => 7,
+            };
+        self.draws.push(Draw::Block { x, y, piece });
+    }
+
+    fn draw_score(&mut self, score: u32) {
+        self.draws.push(Draw::Score(score));
+    }
+
+    fn draw_level(&mut self, level: usize) {
+        self.draws.push(Draw::Level(level));
+    }
+}
+
+/// Play a game the way the main loop does: every frame poll `source`, run the game and draw it.
+/// Stops after `frames` frames or when the game is over.
+pub fn play_game<R: Rng>(source: &mut impl InputSource, rng: &mut R, frames: usize) -> Vec<Draw> {
+    let mut drawing : Drawing = Default::default();
+    let mut game = Game::new(rng);
+    let mut input : Input = Default::default();
+    for _ in 0..frames {
+        source.poll(&mut input);
+        let state = game.run_loop(&input, rng);
+        game.draw(&mut drawing);
+        if matches!(state, GameState::GameOver) {
+            break;
+        }
+    }
+    drawing.draws
+}
+
+/// `frames` frame bytes of somebody mashing every button, with a few breaks in between
+pub fn busy_script(frames: usize) -> Vec<u8> {
+    let moves = [
+        Input { left: true, ..Default::default() },
+        Input { cw_rotate: true, ..Default::default() },
+        Input { down: true, ..Default::default() },
+        Default::default(),
+        Input { right: true, ccw_rotate: true, ..Default::default() },
+        Input { right: true, ..Default::default() },
+        Input { left: true, down: true, ..Default::default() },
+    ];
+    (0..frames).map(|i| input::encode(&moves[i * i / 3 % moves.len()])).collect()
+}
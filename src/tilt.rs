@@ -0,0 +1,138 @@
+use fourtris::game::Input;
+
+use crate::adc::AdcSnapshot;
+use crate::controls::BoosterPack;
+use crate::input::{Actions, InputSource};
+use crate::settings::Sensitivity;
+
+// The accelerometer (KXTC9-2050 on the BoosterPack) is sampled along with the joystick,
+// see adc.rs. Only the X and Y axes are used.
+
+/// number of frames averaged together to find the rest orientation
+const CALIBRATION_FRAMES : i32 = 64;
+/// filtered values are kept with 4 extra bits of precision
+const FILTER_SHIFT : i32 = 4;
+/// how quickly the low pass filter follows the raw readings, bigger is slower
+const FILTER_STRENGTH : i32 = 3;
+/// how far a raw reading needs to jump away from the filtered reading to count as a flick.
+/// the accelerometer gives roughly 800 counts per g
+const FLICK_THRESHOLD : i32 = 400 << FILTER_SHIFT;
+/// frames to ignore flicks for after one is detected, so the board coming back to rest
+/// doesn't rotate the piece right back
+const FLICK_COOLDOWN_FRAMES : u8 = 15;
+
+/// Tilt the board left and right to move the piece, flick it away from or towards you to rotate.
+/// The joystick button and S1/S2 still work, so restarting and pausing behave the same.
+pub struct Tilt {
+    booster_pack: BoosterPack,
+    /// low pass filtered readings (with FILTER_SHIFT extra bits)
+    x: i32,
+    y: i32,
+    /// readings when the board is sitting at rest (with FILTER_SHIFT extra bits)
+    rest_x: i32,
+    rest_y: i32,
+    /// how far the board needs to be tilted to move a piece (with FILTER_SHIFT extra bits)
+    threshold: i32,
+    flick_cooldown: u8,
+    /// frames left before the rest orientation is known, tilting does nothing until then
+    calibration_frames: i32,
+    sum_x: i32,
+    sum_y: i32,
+}
+
+impl Tilt {
+    /// The board gets calibrated during the first second of polling, it should be held
+    /// the way the player wants to hold it during that time.
+    pub fn new(booster_pack: BoosterPack, sensitivity: Sensitivity) -> Tilt {
+        let mut tilt = Tilt {
+            booster_pack,
+            x: 0,
+            y: 0,
+            rest_x: 0,
+            rest_y: 0,
+            threshold: 0,
+            flick_cooldown: 0,
+            calibration_frames: 0,
+            sum_x: 0,
+            sum_y: 0,
+        };
+        tilt.set_sensitivity(sensitivity);
+        tilt.calibrate();
+        tilt
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: Sensitivity) {
+        // roughly 25, 15 and 9 degrees
+        let counts =
+            match sensitivity {
+                Sensitivity::Low => 350,
+                Sensitivity::Medium => 200,
+                Sensitivity::High => 120,
+            };
+        self.threshold = counts << FILTER_SHIFT;
+    }
+
+    /// See BoosterPack::set_snapshot
+    pub fn set_snapshot(&mut self, snapshot: AdcSnapshot) {
+        self.booster_pack.set_snapshot(snapshot);
+    }
+
+    /// Treat the orientation of the board over the next CALIBRATION_FRAMES polls as "level"
+    pub fn calibrate(&mut self) {
+        self.calibration_frames = CALIBRATION_FRAMES;
+        self.sum_x = 0;
+        self.sum_y = 0;
+    }
+}
+
+impl InputSource for Tilt {
+    fn poll(&mut self, input: &mut Input) -> Actions {
+        let actions = self.booster_pack.poll(input);
+
+        let snapshot = self.booster_pack.snapshot();
+        let raw_x = (snapshot.accel_x as i32) << FILTER_SHIFT;
+        let raw_y = (snapshot.accel_y as i32) << FILTER_SHIFT;
+
+        if self.calibration_frames > 0 {
+            self.sum_x += raw_x;
+            self.sum_y += raw_y;
+            self.calibration_frames -= 1;
+            if self.calibration_frames == 0 {
+                self.rest_x = self.sum_x / CALIBRATION_FRAMES;
+                self.rest_y = self.sum_y / CALIBRATION_FRAMES;
+                self.x = self.rest_x;
+                self.y = self.rest_y;
+            }
+            // the joystick is still in charge until we know which way is level
+            return actions;
+        }
+
+        // low pass filter the readings to get the orientation of the board
+        self.x += (raw_x - self.x) >> FILTER_STRENGTH;
+        self.y += (raw_y - self.y) >> FILTER_STRENGTH;
+
+        // a quick jerk shows up as the raw reading jumping away from the filtered one
+        let flick = raw_y - self.y;
+        if self.flick_cooldown > 0 {
+            self.flick_cooldown -= 1;
+        } else if flick > FLICK_THRESHOLD {
+            input.cw_rotate = true;
+            self.flick_cooldown = FLICK_COOLDOWN_FRAMES;
+        } else if flick < -FLICK_THRESHOLD {
+            input.ccw_rotate = true;
+            self.flick_cooldown = FLICK_COOLDOWN_FRAMES;
+        }
+
+        // tilting the left side down lowers the X reading
+        // (on top of the joystick, which keeps working)
+        let tilt = self.x - self.rest_x;
+        input.left |= tilt < -self.threshold;
+        input.right |= tilt > self.threshold;
+
+        actions
+    }
+
+    fn noise_bit(&self) -> Option<usize> {
+        self.booster_pack.noise_bit()
+    }
+}
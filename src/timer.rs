@@ -1,47 +1,494 @@
-pub struct Timer0<'a> {
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use cortex_m::peripheral::NVIC;
+use tm4c123x::{interrupt, Interrupt};
+use tm4c123x::timer0::RegisterBlock;
+
+// Driver for the general purpose timers: TIMER0-5 (32 bits, or two 16 bit halves) and
+// WTIMER0-5 (64 bits, or two 32 bit halves). They all have the same registers.
+//
+// Only timer A of each module is used, timer B is left alone. Periodic and one-shot timers
+// are 32 bits either way. PWM and edge time need the module split in half, so they get
+// 24 bits on TIMERn (16 + 8 bit prescaler) and 48 on WTIMERn (32 + 16 bit prescaler).
+//
+// Each module can only be taken once, so two parts of the program can't end up fighting
+// over the same timer.
+
+// every module is here so any of them can be taken, the game only uses a few
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimerId {
+    Timer0,
+    Timer1,
+    Timer2,
+    Timer3,
+    Timer4,
+    Timer5,
+    WideTimer0,
+    WideTimer1,
+    WideTimer2,
+    WideTimer3,
+    WideTimer4,
+    WideTimer5,
+}
+
+impl TimerId {
+    /// 0-5 for TIMERn, 6-11 for WTIMERn
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn is_wide(self) -> bool {
+        self.index() >= 6
+    }
+
+    /// bit for this module in RCGCTIMER/PRTIMER or RCGCWTIMER/PRWTIMER
+    fn clock_bit(self) -> u32 {
+        1 << (self.index() % 6)
+    }
+
+    fn base_address(self) -> usize {
+        match self {
+            TimerId::Timer0 => 0x4003_0000,
+            TimerId::Timer1 => 0x4003_1000,
+            TimerId::Timer2 => 0x4003_2000,
+            TimerId::Timer3 => 0x4003_3000,
+            TimerId::Timer4 => 0x4003_4000,
+            TimerId::Timer5 => 0x4003_5000,
+            TimerId::WideTimer0 => 0x4003_6000,
+            TimerId::WideTimer1 => 0x4003_7000,
+            TimerId::WideTimer2 => 0x4004_C000,
+            TimerId::WideTimer3 => 0x4004_D000,
+            TimerId::WideTimer4 => 0x4004_E000,
+            TimerId::WideTimer5 => 0x4004_F000,
+        }
+    }
+
+    /// The interrupt for timer A
+    pub fn interrupt(self) -> Interrupt {
+        match self {
+            TimerId::Timer0 => Interrupt::TIMER0A,
+            TimerId::Timer1 => Interrupt::TIMER1A,
+            TimerId::Timer2 => Interrupt::TIMER2A,
+            TimerId::Timer3 => Interrupt::TIMER3A,
+            TimerId::Timer4 => Interrupt::TIMER4A,
+            TimerId::Timer5 => Interrupt::TIMER5A,
+            TimerId::WideTimer0 => Interrupt::WTIMER0A,
+            TimerId::WideTimer1 => Interrupt::WTIMER1A,
+            TimerId::WideTimer2 => Interrupt::WTIMER2A,
+            TimerId::WideTimer3 => Interrupt::WTIMER3A,
+            TimerId::WideTimer4 => Interrupt::WTIMER4A,
+            TimerId::WideTimer5 => Interrupt::WTIMER5A,
+        }
+    }
+
+    /// The registers for this module. Every GPTM has the same layout as TIMER0.
+    /// This is for interrupt handlers, which can't get at the Timer. Everyone else goes
+    /// through the Timer they took, and handlers only touch the timer their owner set up.
+    pub(crate) fn registers(self) -> &'static RegisterBlock {
+        unsafe { &*(self.base_address() as *const RegisterBlock) }
+    }
+}
+
+/// One bit per TimerId, set while someone owns the timer
+static TAKEN : AtomicU16 = AtomicU16::new(0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+// nothing uses edge time yet
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+// nothing uses one-shot or edge time yet
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// count to the interval and start over
+    Periodic,
+    /// count to the interval and stop
+    OneShot,
+    /// output a PWM signal on the TnCCP0 pin: high when the count is above the match value,
+    /// low below it. Always counts down.
+    Pwm,
+    /// save the count every time the TnCCP0 pin sees `Edge`
+    EdgeTime(Edge),
+}
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub mode: Mode,
+    /// ignored for PWM, which always counts down
+    pub direction: Direction,
+    /// ticks per period (the load value)
+    pub interval: u32,
+    /// periodic only: save the free running count in TnR when the timer times out
+    pub snapshot: bool,
+    /// PWM only: flip the output
+    pub invert: bool,
+}
+
+impl Config {
+    /// A periodic timer counting up, which is what most things want
+    pub fn periodic(interval: u32) -> Config {
+        Config {
+            mode: Mode::Periodic,
+            direction: Direction::Up,
+            interval,
+            snapshot: false,
+            invert: false,
+        }
+    }
+
+    /// A PWM signal with a period of `interval` ticks
+    pub fn pwm(interval: u32) -> Config {
+        Config {
+            mode: Mode::Pwm,
+            direction: Direction::Down,
+            interval,
+            snapshot: false,
+            invert: false,
+        }
+    }
+}
+
+// register bits
+const CTL_TAEN : u32 = 1;
+const CTL_TAEVENT_SHIFT : u32 = 2;
+const CTL_TAOTE : u32 = 0x20;
+const CTL_TAPWML : u32 = 0x40;
+const TAMR_ONE_SHOT : u32 = 1;
+const TAMR_PERIODIC : u32 = 2;
+const TAMR_CAPTURE : u32 = 3;
+const TAMR_TACMR : u32 = 0x4;
+const TAMR_TAAMS : u32 = 0x8;
+const TAMR_TACDIR : u32 = 0x10;
+const TAMR_TASNAPS : u32 = 0x80;
+/// timer A timeout in the interrupt registers, for interrupt handlers to clear in ICR
+pub const TIMEOUT : u32 = 0x1;
+const CAPTURE_EVENT : u32 = 0x4;
+
+pub struct Timer<'a> {
     p: &'a tm4c123x::Peripherals,
+    id: TimerId,
+    split: bool,
 }
 
+impl<'a> Timer<'a> {
+    /// Get exclusive use of a timer and turn on its clock. Returns None if someone else has it.
+    pub fn take(p: &'a tm4c123x::Peripherals, id: TimerId) -> Option<Timer<'a>> {
+        let bit = 1 << id.index();
+        if TAKEN.fetch_or(bit, Ordering::AcqRel) & bit != 0 {
+            return None;
+        }
 
-impl Timer0<'_> {
-    pub fn new(p: &tm4c123x::Peripherals, interval_value: u32) -> Timer0 {
-        // 0. Set the Timer0 bit in the RCGCTIMER register
-        p.SYSCTL.rcgctimer.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        // turn on the clock, and wait for the timer to be ready for access
+        if id.is_wide() {
+            p.SYSCTL.rcgcwtimer.modify(|r, w| unsafe { w.bits( r.bits() | id.clock_bit() ) });
+            while p.SYSCTL.prwtimer.read().bits() & id.clock_bit() == 0 {}
+        } else {
+            p.SYSCTL.rcgctimer.modify(|r, w| unsafe { w.bits( r.bits() | id.clock_bit() ) });
+            while p.SYSCTL.prtimer.read().bits() & id.clock_bit() == 0 {}
+        }
 
-        // wait for the timer to be ready for access
-        while p.SYSCTL.prtimer.read().bits() & 1 != 1 {}
+        Some(Timer {
+            p,
+            id,
+            split: false,
+        })
+    }
+
+    fn registers(&self) -> &'static RegisterBlock {
+        self.id.registers()
+    }
+
+    /// Set the timer up, it's left stopped
+    pub fn configure(&mut self, config: &Config) {
+        let timer = self.registers();
 
         // 1. Ensure timer is disabled
-        p.TIMER0.ctl.modify(|r, w| unsafe { w.bits( r.bits() & (!1) ) });
+        timer.ctl.modify(|r, w| unsafe { w.bits( r.bits() & !(CTL_TAEN | CTL_TAOTE | CTL_TAPWML) ) });
 
-        // 2. Write 0 to the GPTM configuration register
-        //    this gives us a 32 bit timer
-        p.TIMER0.cfg.write(|w| unsafe { w.bits(0) });
+        // 2. Write the GPTM configuration register
+        //    0 = one 32 bit timer (64 on the wide ones), 4 = split into A and B
+        //    the wide timers are always split so timer A is 32 bits like everyone else
+        self.split = self.id.is_wide() || match config.mode {
+            Mode::Pwm | Mode::EdgeTime(_) => true,
+            Mode::Periodic | Mode::OneShot => false,
+        };
+        timer.cfg.write(|w| unsafe { w.bits( if self.split { 4 } else { 0 } ) });
 
-        // 3. Place timer A in periodic mode
-        p.TIMER0.tamr.modify(|r, w| unsafe { w.bits( (r.bits() & (!3)) | 2 ) });
+        // 3. Timer A mode
+        let mut tamr = match config.mode {
+            Mode::OneShot => TAMR_ONE_SHOT,
+            Mode::Periodic => TAMR_PERIODIC,
+            Mode::Pwm => TAMR_PERIODIC | TAMR_TAAMS,
+            Mode::EdgeTime(_) => TAMR_CAPTURE | TAMR_TACMR,
+        };
+        if config.direction == Direction::Up && config.mode != Mode::Pwm {
+            tamr |= TAMR_TACDIR;
+        }
+        if config.snapshot && config.mode == Mode::Periodic {
+            tamr |= TAMR_TASNAPS;
+        }
+        timer.tamr.write(|w| unsafe { w.bits(tamr) });
 
-        // 4. TASNAPS and TACDIR - take a snapshot and use count up mode
-        p.TIMER0.tamr.modify(|r, w| unsafe { w.bits( r.bits() | 0x90 ) });
+        // 4. PWM polarity and which edges get timed
+        match config.mode {
+            Mode::Pwm if config.invert => {
+                timer.ctl.modify(|r, w| unsafe { w.bits( r.bits() | CTL_TAPWML ) });
+            },
+            Mode::EdgeTime(edge) => {
+                let event = match edge {
+                    Edge::Rising => 0,
+                    Edge::Falling => 1,
+                    Edge::Both => 3,
+                };
+                timer.ctl.modify(|r, w| unsafe {
+                    w.bits( (r.bits() & !(3 << CTL_TAEVENT_SHIFT)) | (event << CTL_TAEVENT_SHIFT) )
+                });
+            },
+            _ => {},
+        }
 
-        // 5. Load start value into TnILR register
-        p.TIMER0.tailr.write(|w| unsafe { w.bits(interval_value) });
+        // 5. Load start value
+        self.set_interval(config.interval);
+        if config.mode == Mode::Pwm {
+            // start out with a 0% duty cycle
+            self.set_match(config.interval);
+        }
+    }
 
-        Timer0 {
-            p
+    /// Change the load value. In split mode the prescaler holds the bits that
+    /// don't fit in the timer (bits 16-23 on TIMERn, 32-47 on WTIMERn, so up to 32 here).
+    pub fn set_interval(&self, interval: u32) {
+        let timer = self.registers();
+        if self.split && !self.id.is_wide() {
+            timer.tapr.write(|w| unsafe { w.bits( interval >> 16 ) });
+            timer.tailr.write(|w| unsafe { w.bits( interval & 0xFFFF ) });
+        } else {
+            timer.tailr.write(|w| unsafe { w.bits(interval) });
+        }
+    }
+
+    /// PWM: the output goes low when the count drops below this (counting down from the interval),
+    /// so `interval - duty_ticks` gives `duty_ticks` high ticks per period
+    pub fn set_match(&self, value: u32) {
+        let timer = self.registers();
+        if self.split && !self.id.is_wide() {
+            timer.tapmr.write(|w| unsafe { w.bits( value >> 16 ) });
+            timer.tamatchr.write(|w| unsafe { w.bits( value & 0xFFFF ) });
+        } else {
+            timer.tamatchr.write(|w| unsafe { w.bits(value) });
         }
     }
 
     pub fn start(&self) {
-        // 7. Enable timer
-        self.p.TIMER0.ctl.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        self.registers().ctl.modify(|r, w| unsafe { w.bits( r.bits() | CTL_TAEN ) });
     }
 
-    pub fn timeout_occured(&self) -> bool {
-        self.p.TIMER0.ris.read().bits() & 1 == 1
+    pub fn stop(&self) {
+        self.registers().ctl.modify(|r, w| unsafe { w.bits( r.bits() & !CTL_TAEN ) });
     }
 
+    /// Start the count over. Only works while the timer is stopped.
+    pub fn reset_count(&self, value: u32) {
+        self.registers().tav.write(|w| unsafe { w.bits(value) });
+    }
+
+    /// The free running count
+    pub fn value(&self) -> u32 {
+        self.registers().tav.read().bits()
+    }
+
+    /// Edge time: the count when the last edge was seen.
+    /// Periodic with snapshot: the count when the timer last timed out.
+    #[allow(dead_code)]
+    pub fn captured(&self) -> u32 {
+        self.registers().tar.read().bits()
+    }
+
+    /// Trigger the ADC every time the timer times out
+    pub fn enable_adc_trigger(&self) {
+        self.registers().ctl.modify(|r, w| unsafe { w.bits( r.bits() | CTL_TAOTE ) });
+    }
+
+    /// Fire timer A's interrupt on timeouts (and on edges in edge time mode).
+    /// Whoever owns the timer has to provide the interrupt handler.
+    pub fn enable_interrupt(&self) {
+        let timer = self.registers();
+        timer.icr.write(|w| unsafe { w.bits( TIMEOUT | CAPTURE_EVENT ) });
+        timer.imr.modify(|r, w| unsafe { w.bits( r.bits() | TIMEOUT | CAPTURE_EVENT ) });
+        unsafe {
+            NVIC::unmask(self.id.interrupt());
+        }
+    }
+
+    pub fn disable_interrupt(&self) {
+        NVIC::mask(self.id.interrupt());
+        self.registers().imr.modify(|r, w| unsafe { w.bits( r.bits() & !(TIMEOUT | CAPTURE_EVENT) ) });
+    }
+
+    /// Timed out (or saw an edge) since the last clear_interrupt.
+    /// For polling a one-shot timer, nothing does that yet.
+    #[allow(dead_code)]
+    pub fn timed_out(&self) -> bool {
+        self.registers().ris.read().bits() & (TIMEOUT | CAPTURE_EVENT) != 0
+    }
+
+    #[allow(dead_code)]
     pub fn clear_interrupt(&self) {
-        self.p.TIMER0.icr.write(|w| unsafe { w.bits(1) });
+        self.registers().icr.write(|w| unsafe { w.bits( TIMEOUT | CAPTURE_EVENT ) });
     }
 }
+
+impl Drop for Timer<'_> {
+    /// Stop the timer and let someone else have it
+    fn drop(&mut self) {
+        self.disable_interrupt();
+        self.stop();
+        let bit = 1 << self.id.index();
+        if self.id.is_wide() {
+            self.p.SYSCTL.rcgcwtimer.modify(|r, w| unsafe { w.bits( r.bits() & !self.id.clock_bit() ) });
+        } else {
+            self.p.SYSCTL.rcgctimer.modify(|r, w| unsafe { w.bits( r.bits() & !self.id.clock_bit() ) });
+        }
+        TAKEN.fetch_and(!bit, Ordering::AcqRel);
+    }
+}
+
+/// Number of times Timer0 has timed out, only written by the interrupt
+static FRAME_COUNT : AtomicU32 = AtomicU32::new(0);
+
+/// A moment since Timer0 started: the frame, and how far into it. Later moments compare greater.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FrameTime {
+    pub frame: u32,
+    /// clock ticks since the frame started
+    pub ticks: u32,
+}
+
+impl FrameTime {
+    /// Clock ticks from `earlier` to this. Whole frames are counted at the current frame length,
+    /// so it's a bit off if the frame rate changed in between.
+    pub fn ticks_since(self, earlier: FrameTime) -> u32 {
+        let frame_ticks = TimerId::Timer0.registers().tailr.read().bits();
+        self.frame.wrapping_sub(earlier.frame)
+            .wrapping_mul(frame_ticks)
+            .wrapping_add(self.ticks)
+            .wrapping_sub(earlier.ticks)
+    }
+}
+
+/// What time it is, by the frame tick. Works from interrupt handlers too.
+pub fn frame_time() -> FrameTime {
+    let timer = TimerId::Timer0.registers();
+    let timed_out = || timer.ris.read().bits() & TIMEOUT != 0;
+    loop {
+        let frame = FRAME_COUNT.load(Ordering::Relaxed);
+        let before = timed_out();
+        let ticks = timer.tav.read().bits();
+        let after = timed_out();
+        // the count starting over or TIMER0A running in the middle of all that could pair
+        // the ticks with the wrong frame, so try again
+        if before != after || FRAME_COUNT.load(Ordering::Relaxed) != frame {
+            continue;
+        }
+        // a timeout TIMER0A hasn't gotten to yet (because it can't interrupt whoever is
+        // asking) still counts as a new frame
+        let frame = if after { frame.wrapping_add(1) } else { frame };
+        return FrameTime { frame, ticks };
+    }
+}
+
+/// The frame tick: TIMER0 counting up, periodic, with an interrupt every frame.
+/// It has to be TIMER0, `frame_time` reads it directly.
+pub struct Timer0<'a> {
+    timer: Timer<'a>,
+    /// the frame the main loop is on
+    frame: Cell<u32>,
+    /// number of frames that were skipped because the main loop took too long (handy in the debugger)
+    late_frames: Cell<u32>,
+}
+
+impl<'a> Timer0<'a> {
+    pub fn new(p: &'a tm4c123x::Peripherals, interval_value: u32) -> Timer0<'a> {
+        let mut timer = Timer::take(p, TimerId::Timer0).unwrap();
+        let mut config = Config::periodic(interval_value);
+        config.snapshot = true;
+        timer.configure(&config);
+        timer.enable_interrupt();
+
+        Timer0 {
+            timer,
+            frame: Cell::new(FRAME_COUNT.load(Ordering::Relaxed)),
+            late_frames: Cell::new(0),
+        }
+    }
+
+    pub fn start(&self) {
+        self.timer.start();
+    }
+
+    /// Stop counting frames, `start` picks up where it left off
+    pub fn stop(&self) {
+        self.timer.stop();
+    }
+
+    /// Sleep until the next frame starts. Any interrupt wakes the CPU up, but it goes right
+    /// back to sleep unless the frame counter has moved on.
+    /// Returns the number of frames that have gone by, anything more than 1 means we were late.
+    pub fn wait_for_next_frame(&self) -> u32 {
+        loop {
+            // with interrupts masked, the timer can't sneak in between the check and the WFI.
+            // A pending interrupt still wakes the CPU, and runs as soon as they're unmasked.
+            cortex_m::interrupt::disable();
+            let now = FRAME_COUNT.load(Ordering::Relaxed);
+            if now != self.frame.get() {
+                unsafe { cortex_m::interrupt::enable() };
+                let elapsed = now.wrapping_sub(self.frame.get());
+                self.late_frames.set(self.late_frames.get().wrapping_add(elapsed - 1));
+                self.frame.set(now);
+                return elapsed;
+            }
+            cortex_m::asm::wfi();
+            unsafe { cortex_m::interrupt::enable() };
+        }
+    }
+
+    /// Change how long a frame is. The current frame is cut short, and the next one starts now.
+    pub fn set_interval(&self, interval_value: u32) {
+        // stop the timer, so it can't time out halfway through
+        self.timer.stop();
+        self.timer.set_interval(interval_value);
+        // start counting from 0, in case we were already past the new interval
+        self.timer.reset_count(0);
+        self.timer.start();
+    }
+
+    /// Trigger the ADC every time the timer times out
+    pub fn enable_adc_trigger(&self) {
+        self.timer.enable_adc_trigger();
+    }
+
+    /// Number of ticks since the timer last timed out
+    pub fn value(&self) -> u32 {
+        self.timer.value()
+    }
+}
+
+#[interrupt]
+fn TIMER0A() {
+    let timer = TimerId::Timer0.registers();
+    timer.icr.write(|w| unsafe { w.bits(TIMEOUT) });
+    FRAME_COUNT.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,97 @@
+use crate::clock;
+
+/// Bits per second, 8N1
+const BAUD_RATE : u32 = 115_200;
+
+// Pins used for UART0 (goes over the debugger's USB connection as a virtual COM port)
+// PA0 - U0Rx
+// PA1 - U0Tx
+pub struct Uart0<'a> {
+    p: &'a tm4c123x::Peripherals,
+}
+
+impl Uart0<'_> {
+    const RX_FIFO_EMPTY : u32 = 0x10;
+    const TX_FIFO_FULL  : u32 = 0x20;
+
+    pub fn new(p: &tm4c123x::Peripherals) -> Uart0 {
+        // 1. enable the clock for UART0
+        p.SYSCTL.rcgcuart.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+        // 2. enable the clock for port A
+        p.SYSCTL.rcgcgpio.modify(|r, w| unsafe { w.bits( r.bits() | 1 ) });
+
+        // wait for UART0 and port A to be ready for use
+        while p.SYSCTL.pruart.read().bits() & 1 != 1 {}
+        while p.SYSCTL.prgpio.read().bits() & 1 != 1 {}
+
+        // 3. enable alternate functions on PA0 and PA1
+        p.GPIO_PORTA_AHB.afsel.modify(|r, w| unsafe { w.bits( r.bits() | 0b11 ) });
+        // 4. select U0Rx and U0Tx (PMC value of 1) for PA0 and PA1
+        p.GPIO_PORTA_AHB.pctl.modify(|r, w| unsafe { w.bits( (r.bits() & !0xFF) | 0x11 ) });
+        // 5. digital, not analog
+        p.GPIO_PORTA_AHB.amsel.modify(|r, w| unsafe { w.bits( r.bits() & !0b11 ) });
+        p.GPIO_PORTA_AHB.den.modify(|r, w| unsafe { w.bits( r.bits() | 0b11 ) });
+
+        // 6. disable the UART while we configure it
+        p.UART0.ctl.modify(|r, w| unsafe { w.bits( r.bits() & !1 ) });
+        // 7. baud rate divisor = system clock / (16 * baud rate)
+        //    e.g. 80_000_000 / (16 * 115_200) = 43.4028
+        //    integer part = 43, fractional part = round(0.4028 * 64) = 26
+        let (integer, fraction) = clock::uart_divisors(BAUD_RATE);
+        p.UART0.ibrd.write(|w| unsafe { w.bits(integer) });
+        p.UART0.fbrd.write(|w| unsafe { w.bits(fraction) });
+        // 8. 8 data bits (WLEN = 0b11), no parity, one stop bit, FIFOs enabled
+        p.UART0.lcrh.write(|w| unsafe { w.bits( (3 << 5) | (1 << 4) ) });
+        // 9. use the system clock
+        p.UART0.cc.write(|w| unsafe { w.bits(0) });
+        // 10. enable the UART, transmitter and receiver
+        p.UART0.ctl.modify(|r, w| unsafe { w.bits( r.bits() | (1 << 9) | (1 << 8) | 1 ) });
+
+        Uart0 {
+            p
+        }
+    }
+
+    /// Returns true if there is at least one byte waiting to be read
+    pub fn byte_available(&self) -> bool {
+        self.p.UART0.fr.read().bits() & Uart0::RX_FIFO_EMPTY == 0
+    }
+
+    /// Grab a byte out of the receive FIFO, if there is one
+    pub fn read_byte(&self) -> Option<u8> {
+        if self.byte_available() {
+            Some((self.p.UART0.dr.read().bits() & 0xFF) as u8)
+        } else {
+            None
+        }
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        // wait for space in the transmit FIFO
+        while self.p.UART0.fr.read().bits() & Uart0::TX_FIFO_FULL != 0 {}
+        self.p.UART0.dr.write(|w| unsafe { w.bits(byte as u32) });
+    }
+
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        for b in bytes {
+            self.write_byte(*b);
+        }
+    }
+
+    /// Write a number as decimal digits
+    pub fn write_decimal(&self, value: u32) {
+        // u32::MAX has 10 digits
+        let mut digits = [0u8; 10];
+        let mut start = digits.len();
+        let mut value = value;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        self.write_bytes(&digits[start..]);
+    }
+}